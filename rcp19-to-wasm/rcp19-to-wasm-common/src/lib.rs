@@ -28,3 +28,22 @@ pub enum RuleAction {
     SetDisplay,
     SetRequired,
 }
+
+/// The contents of the `reso.metadata` custom section, describing the module it's in
+#[derive(Serialize, Deserialize)]
+pub struct Metadata {
+    /// The MLS whose rules the module implements
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mls_id: Option<String>,
+    /// The version of the MLS' rules the module was built from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules_version: Option<String>,
+    /// When the module was built, as an RFC 3339 timestamp
+    pub build_timestamp: String,
+    /// The tool that built the module, and its version
+    pub generator: String,
+    /// The RESO resources the module knows how to validate
+    pub resources: Vec<String>,
+    /// A short description of each rule, in order, for coverage reports
+    pub rules: Vec<String>,
+}
@@ -0,0 +1,22 @@
+//! Counting how often each rule is evaluated and takes its action, for coverage reports
+//!
+//! `rcp19-to-wasm` replaces these imports with functions that bump a counter for the rule, so they
+//! never reach the host.
+
+/// Count a rule being evaluated
+pub fn hit(rule: usize) {
+    unsafe { sys::rule_hit(rule as i32) }
+}
+
+/// Count a rule taking its action, such as reporting an error
+pub fn fired(rule: usize) {
+    unsafe { sys::rule_fired(rule as i32) }
+}
+
+mod sys {
+    #[link(wasm_import_module = "rcp19")]
+    extern "C" {
+        pub fn rule_hit(rule: i32);
+        pub fn rule_fired(rule: i32);
+    }
+}
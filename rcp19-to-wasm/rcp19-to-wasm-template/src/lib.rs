@@ -1,6 +1,7 @@
 use rcp19_to_wasm_common::{RuleAction, Rules};
 use std::collections::BTreeMap;
 
+mod coverage;
 mod reso;
 
 #[no_mangle]
@@ -49,10 +50,12 @@ pub extern "C" fn validate_target(ptr: *const u8, len: usize) {
         context.set_previous(previous_data.as_ref());
 
         let result = expression.apply_with_locals(context, &locals);
+        coverage::hit(idx);
 
         match rule.rule_action {
             RuleAction::Evaluate => match result {
                 Ok(value) => {
+                    coverage::fired(idx);
                     reso::diagnostic(&format!(
                         "{}: {}",
                         rule.rule_message,
@@ -69,12 +72,14 @@ pub extern "C" fn validate_target(ptr: *const u8, len: usize) {
                 }
                 _ => {
                     // Reject if any other value or an error
+                    coverage::fired(idx);
                     reso::error(&rule.field_name, &rule.rule_message);
                 }
             },
             RuleAction::Reject => match result {
                 Ok(value) if value.as_bool() == Some(true) => {
                     // Reject if true
+                    coverage::fired(idx);
                     reso::error(&rule.field_name, &rule.rule_message);
                 }
                 _ => {
@@ -84,6 +89,7 @@ pub extern "C" fn validate_target(ptr: *const u8, len: usize) {
             RuleAction::Warning => match result {
                 Ok(value) if value.as_bool() == Some(true) => {
                     // Warn if true
+                    coverage::fired(idx);
                     reso::warn(&rule.field_name, &rule.rule_message);
                 }
                 _ => {
@@ -93,6 +99,7 @@ pub extern "C" fn validate_target(ptr: *const u8, len: usize) {
             RuleAction::SetRequired => match result {
                 Ok(value) if value.as_bool() == Some(true) => {
                     // Set required if true
+                    coverage::fired(idx);
                     reso::set_required(&rule.field_name, true);
                 }
                 _ => {
@@ -103,6 +110,7 @@ pub extern "C" fn validate_target(ptr: *const u8, len: usize) {
             RuleAction::SetDisplay => match result {
                 Ok(value) if value.as_bool() == Some(true) => {
                     // Set display if true
+                    coverage::fired(idx);
                     reso::set_display(&rule.field_name, true);
                 }
                 _ => {
@@ -112,6 +120,7 @@ pub extern "C" fn validate_target(ptr: *const u8, len: usize) {
             },
             RuleAction::Set => match result {
                 Ok(value) => {
+                    coverage::fired(idx);
                     reso::set(&rule.field_name, value.as_ref());
                     locals.insert(&rule.field_name, value);
                 }
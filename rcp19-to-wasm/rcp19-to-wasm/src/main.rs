@@ -1,6 +1,9 @@
 use clap::Parser;
-use rcp19_to_wasm_common::Rules;
-use std::path::PathBuf;
+use rcp19_to_wasm_common::{Metadata, Rule, Rules};
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 const WASM: &[u8] = include_bytes!("../template.wasm");
 
@@ -15,8 +18,26 @@ struct Args {
     /// The destination to write the wasm file to
     #[arg(short, long, value_name = "FILE")]
     output: PathBuf,
+
+    /// The MLS whose rules these are, recorded in the module's metadata
+    #[arg(long)]
+    mls_id: Option<String>,
+
+    /// The version of the rules, recorded in the module's metadata
+    #[arg(long)]
+    rules_version: Option<String>,
+
+    /// A RESO resource the rules apply to, recorded in the module's metadata
+    #[arg(long = "resource", value_name = "RESOURCE", default_value = "Property")]
+    resources: Vec<String>,
 }
 
+/// The custom section the module's metadata is written to
+const METADATA_SECTION: &str = "reso.metadata";
+
+/// The import module the template counts rule coverage through
+const COVERAGE_MODULE: &str = "rcp19";
+
 const WASM_PAGE_SIZE: u32 = 65536;
 
 fn main() {
@@ -26,7 +47,31 @@ fn main() {
     let rules: Rules =
         serde_json::from_slice(&rules_data).expect("Expected rules file to be valid JSON");
     validate_rules(&rules);
-    let rules_data = serde_json::to_vec(&rules).unwrap();
+    let mut module = build(&rules);
+
+    // Record what the module is, so it isn't just an opaque blob to whoever ends up with it.
+    let metadata = Metadata {
+        mls_id: args.mls_id,
+        rules_version: args.rules_version,
+        build_timestamp: build_timestamp(),
+        generator: concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")).to_string(),
+        resources: args.resources,
+        rules: rules.value.iter().map(describe_rule).collect(),
+    };
+    module.customs.add(walrus::RawCustomSection {
+        name: METADATA_SECTION.to_string(),
+        data: serde_json::to_vec(&metadata).unwrap(),
+    });
+
+    // And write out the wasm file!
+    module
+        .emit_wasm_file(args.output)
+        .expect("Failed to output file");
+}
+
+/// Turn the template into a module that evaluates the given rules
+fn build(rules: &Rules) -> walrus::Module {
+    let rules_data = serde_json::to_vec(rules).unwrap();
 
     let mut module =
         walrus::Module::from_buffer(WASM).expect("Expected wasm blob to be valid wasm");
@@ -88,10 +133,128 @@ fn main() {
     // need to be exported.
     module.exports.delete(validate_target_export_id);
 
-    // And write out the wasm file!
+    add_rule_coverage(&mut module, rules.value.len());
     module
-        .emit_wasm_file(args.output)
-        .expect("Failed to output file");
+}
+
+/// The time of the build as an RFC 3339 timestamp in UTC
+///
+/// Honors `SOURCE_DATE_EPOCH`, so builds can be reproducible.
+fn build_timestamp() -> String {
+    let seconds = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch
+            .parse::<u64>()
+            .expect("Expected SOURCE_DATE_EPOCH to be a number of seconds"),
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Expected the clock to be after 1970")
+            .as_secs(),
+    };
+    rfc3339(seconds)
+}
+
+/// Format a number of seconds since the Unix epoch as an RFC 3339 timestamp in UTC
+fn rfc3339(seconds: u64) -> String {
+    // Convert days since the epoch to a civil date, following
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let time = seconds % 86400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Count how often each rule is evaluated and takes its action
+///
+/// The template calls `rcp19.rule_hit` and `rcp19.rule_fired` with the index of the rule. Each is
+/// replaced with a function that bumps a counter for that rule, in a global exported as
+/// `reso.coverage.rule.<index>.hit` or `.fired`, which the host reads for coverage reports.
+fn add_rule_coverage(module: &mut walrus::Module, rules: usize) {
+    for counter in ["hit", "fired"] {
+        let import = module
+            .imports
+            .get_func(COVERAGE_MODULE, format!("rule_{counter}"))
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Expected the template to import {COVERAGE_MODULE}.rule_{counter}; \
+                     rebuild template.wasm from rcp19-to-wasm-template"
+                )
+            });
+
+        let globals: Vec<_> = (0..rules)
+            .map(|index| {
+                let global = module.globals.add_local(
+                    walrus::ValType::I64,
+                    true,
+                    walrus::InitExpr::Value(walrus::ir::Value::I64(0)),
+                );
+                module
+                    .exports
+                    .add(&format!("reso.coverage.rule.{index}.{counter}"), global);
+                global
+            })
+            .collect();
+
+        module
+            .replace_imported_func(import, |(body, args)| {
+                if globals.is_empty() {
+                    return;
+                }
+                // A `br_table` on the rule's index jumps straight to the code that bumps its
+                // counter, however many rules there are. Block `i` ends just before rule `i`'s
+                // code, which then leaves the outermost block, as does an index out of range.
+                let done = body.dangling_instr_seq(None).id();
+                let blocks: Vec<_> = globals
+                    .iter()
+                    .map(|_| body.dangling_instr_seq(None).id())
+                    .collect();
+                body.instr_seq(blocks[0])
+                    .local_get(args[0])
+                    .br_table(blocks.clone().into(), done);
+                for (index, global) in globals.iter().enumerate() {
+                    let outer = blocks.get(index + 1).copied().unwrap_or(done);
+                    let mut seq = body.instr_seq(outer);
+                    seq.instr(walrus::ir::Block { seq: blocks[index] })
+                        .global_get(*global)
+                        .i64_const(1)
+                        .binop(walrus::ir::BinaryOp::I64Add)
+                        .global_set(*global);
+                    if outer != done {
+                        seq.br(done);
+                    }
+                }
+                body.instr(walrus::ir::Block { seq: done });
+            })
+            .expect("Expected to replace the template's coverage import");
+    }
+}
+
+/// A rule as a single line, such as `ListPrice REJECT: List price must be positive`
+fn describe_rule(rule: &Rule) -> String {
+    let action = serde_json::to_value(&rule.rule_action).unwrap();
+    format!(
+        "{} {}: {}",
+        rule.field_name,
+        action.as_str().unwrap_or_default(),
+        rule.rule_message
+    )
 }
 
 fn validate_rules(rules: &Rules) {
@@ -101,3 +264,233 @@ fn validate_rules(rules: &Rules) {
             .expect("Failed to parse rule");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{add_rule_coverage, build, rfc3339, Rules, COVERAGE_MODULE};
+
+    fn is_leap(year: u64) -> bool {
+        year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+    }
+
+    fn days_in_month(year: u64, month: u64) -> u64 {
+        match month {
+            2 if is_leap(year) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// Seconds since the epoch at midnight on the given day, counting days the slow way
+    fn midnight(year: u64, month: u64, day: u64) -> u64 {
+        let days = (1970..year)
+            .map(|year| if is_leap(year) { 366 } else { 365 })
+            .sum::<u64>()
+            + (1..month)
+                .map(|month| days_in_month(year, month))
+                .sum::<u64>()
+            + day
+            - 1;
+        days * 86400
+    }
+
+    #[test]
+    fn the_epoch() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(86399), "1970-01-01T23:59:59Z");
+    }
+
+    #[test]
+    fn times_of_day() {
+        assert_eq!(rfc3339(1_692_005_400), "2023-08-14T09:30:00Z");
+        assert_eq!(
+            rfc3339(midnight(2024, 3, 1) + 45_296),
+            "2024-03-01T12:34:56Z"
+        );
+    }
+
+    #[test]
+    fn leap_days() {
+        assert_eq!(rfc3339(midnight(2024, 2, 29)), "2024-02-29T00:00:00Z");
+        assert_eq!(rfc3339(midnight(2024, 2, 29) - 1), "2024-02-28T23:59:59Z");
+        assert_eq!(rfc3339(midnight(2023, 3, 1) - 1), "2023-02-28T23:59:59Z");
+        assert_eq!(rfc3339(midnight(2024, 12, 31)), "2024-12-31T00:00:00Z");
+    }
+
+    #[test]
+    fn centuries() {
+        // 2000 is divisible by 400, so it has a leap day; 2100 is only divisible by 100, so it
+        // doesn't.
+        assert_eq!(rfc3339(midnight(2000, 2, 29)), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(midnight(2000, 3, 1)), "2000-03-01T00:00:00Z");
+        assert_eq!(rfc3339(midnight(2100, 3, 1) - 1), "2100-02-28T23:59:59Z");
+        assert_eq!(rfc3339(midnight(2100, 3, 1)), "2100-03-01T00:00:00Z");
+        assert_eq!(rfc3339(midnight(2400, 2, 29)), "2400-02-29T00:00:00Z");
+    }
+
+    #[test]
+    fn every_day_for_five_centuries() {
+        let mut seconds = 0;
+        for year in 1970..2470 {
+            for month in 1..=12 {
+                for day in 1..=days_in_month(year, month) {
+                    assert_eq!(
+                        rfc3339(seconds),
+                        format!("{year:04}-{month:02}-{day:02}T00:00:00Z")
+                    );
+                    seconds += 86400;
+                }
+            }
+        }
+    }
+
+    /// A module whose `validate` counts the given rules as hit, through the template's import
+    fn counting(rules: &[i32]) -> walrus::Module {
+        let mut module = walrus::Module::default();
+        let ty = module.types.add(&[walrus::ValType::I32], &[]);
+        let (rule_hit, _) = module.add_import_func(COVERAGE_MODULE, "rule_hit", ty);
+        module.add_import_func(COVERAGE_MODULE, "rule_fired", ty);
+        let mut builder = walrus::FunctionBuilder::new(&mut module.types, &[], &[]);
+        for rule in rules {
+            builder.func_body().i32_const(*rule).call(rule_hit);
+        }
+        let validate = builder.finish(vec![], &mut module.funcs);
+        module.exports.add("validate", validate);
+        module
+    }
+
+    /// Run a module's `validate` against a listing `runs` times, stubbing out every `reso` import
+    /// but `data`, and return its coverage counters
+    fn run(webassembly: &[u8], listing: serde_json::Value, runs: usize) -> Vec<(String, i64)> {
+        let engine = wasmi::Engine::default();
+        let module = wasmi::Module::new(&engine, webassembly).unwrap();
+        let mut store = wasmi::Store::new(&engine, listing.to_string());
+        let mut linker = wasmi::Linker::new(&engine);
+        for import in module.imports() {
+            let wasmi::ExternType::Func(ty) = import.ty() else {
+                panic!("unexpected import {}", import.name());
+            };
+            let data = import.name() == "data";
+            linker
+                .func_new(
+                    import.module(),
+                    import.name(),
+                    ty.clone(),
+                    move |mut caller, args, results| {
+                        let listing: &String = caller.data();
+                        let listing = listing.clone().into_bytes();
+                        if data && listing.len() <= args[0].i32().unwrap() as usize {
+                            let memory =
+                                caller.get_export("memory").unwrap().into_memory().unwrap();
+                            let ptr = args[1].i32().unwrap() as usize;
+                            memory.write(&mut caller, ptr, &listing).unwrap();
+                        }
+                        if let Some(result) = results.first_mut() {
+                            *result = wasmi::Value::I32(listing.len() as i32);
+                        }
+                        Ok(())
+                    },
+                )
+                .unwrap();
+        }
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        let validate = instance
+            .get_typed_func::<(), ()>(&store, "validate")
+            .unwrap();
+        for _ in 0..runs {
+            validate.call(&mut store, ()).unwrap();
+        }
+
+        let mut counters: Vec<_> = module
+            .exports()
+            .filter_map(|export| export.name().strip_prefix("reso.coverage.rule."))
+            .map(|name| {
+                let global = instance
+                    .get_global(&store, &format!("reso.coverage.rule.{name}"))
+                    .unwrap();
+                (name.to_string(), global.get(&store).i64().unwrap())
+            })
+            .collect();
+        counters.sort();
+        counters
+    }
+
+    #[test]
+    fn rules_are_counted_as_they_are_hit_and_fire() {
+        let rules: Rules = serde_json::from_value(serde_json::json!({
+            "value": [
+                {
+                    "FieldName": "ListPrice",
+                    "RuleAction": "ACCEPT",
+                    "RuleMessage": "List price must be greater than $0",
+                    "RuleExpression": "ListPrice > 0"
+                },
+                {
+                    "FieldName": "ListPrice",
+                    "RuleAction": "REJECT",
+                    "RuleMessage": "List price is too high",
+                    "RuleExpression": "ListPrice > 100000000"
+                },
+                {
+                    "FieldName": "ClosePrice",
+                    "RuleAction": "WARNING",
+                    "RuleMessage": "Closed listings should have a close price",
+                    "RuleExpression": "MlsStatus .IN. SET('Closed')"
+                }
+            ]
+        }))
+        .unwrap();
+        let webassembly = build(&rules).emit_wasm();
+
+        let listing = serde_json::json!({ "ListPrice": 0, "MlsStatus": "Closed" });
+        assert_eq!(
+            run(&webassembly, listing, 2),
+            [
+                ("0.fired".to_string(), 2),
+                ("0.hit".to_string(), 2),
+                ("1.fired".to_string(), 0),
+                ("1.hit".to_string(), 2),
+                ("2.fired".to_string(), 2),
+                ("2.hit".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_rules_are_not_counted() {
+        let mut module = counting(&[0, 2, 2, 3, -1]);
+        add_rule_coverage(&mut module, 3);
+        let webassembly = module.emit_wasm();
+
+        let counters = run(&webassembly, serde_json::Value::Null, 1);
+        let hits: Vec<_> = counters
+            .iter()
+            .filter(|(name, _)| name.ends_with(".hit"))
+            .map(|(_, count)| *count)
+            .collect();
+        assert_eq!(hits, [1, 0, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "rebuild template.wasm")]
+    fn templates_without_coverage_hooks_are_rejected() {
+        let mut module = walrus::Module::default();
+        add_rule_coverage(&mut module, 1);
+    }
+
+    #[test]
+    fn no_rules_means_no_counters() {
+        let mut module = counting(&[0]);
+        add_rule_coverage(&mut module, 0);
+        let webassembly = module.emit_wasm();
+
+        let module = walrus::Module::from_buffer(&webassembly).unwrap();
+        assert_eq!(module.exports.iter().count(), 1);
+        assert_eq!(module.imports.iter().count(), 0);
+    }
+}
@@ -0,0 +1,139 @@
+//! Build modules that call the `reso.*` host functions with arbitrary pointers, lengths and
+//! memory contents, and check that the host always ends up with a trap or an outcome, never a
+//! panic, on every runtime.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use wasm_encoder::{
+    CodeSection, ConstExpr, DataSection, EntityType, ExportKind, ExportSection, Function,
+    FunctionSection, ImportSection, Instruction, MemorySection, MemoryType, Module, TypeSection,
+    ValType,
+};
+use webassembly_rules_poc::{host, Context, Runtime, Validator};
+
+const PAGE_SIZE: usize = 65536;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    data: String,
+    previous_data: String,
+    /// The module's memory, or `None` for a module without one
+    memory: Option<Memory>,
+    calls: Vec<Call>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Memory {
+    /// How many pages the memory starts with, modulo 3
+    pages: u8,
+    /// Bytes to put in memory before `validate` runs, at the given offsets
+    segments: Vec<(u16, Vec<u8>)>,
+}
+
+/// A call to a host function, picked modulo the number of host functions
+#[derive(Arbitrary, Debug)]
+struct Call {
+    function: u8,
+    args: [Arg; 6],
+}
+
+/// An argument to a host call, biased towards values that are near the module's memory
+#[derive(Arbitrary, Debug)]
+enum Arg {
+    Small(u8),
+    Offset(u16),
+    Page(u8, u16),
+    Any(i32),
+}
+
+impl Arg {
+    fn value(&self) -> i32 {
+        match self {
+            Arg::Small(value) => i32::from(*value),
+            Arg::Offset(value) => i32::from(*value),
+            Arg::Page(page, offset) => i32::from(*page % 4) * PAGE_SIZE as i32 + i32::from(*offset),
+            Arg::Any(value) => *value,
+        }
+    }
+}
+
+/// Encode a module that imports every host function and whose `validate` makes the given calls
+fn build(input: &Input) -> Vec<u8> {
+    let signatures = host::signatures();
+
+    let mut types = TypeSection::new();
+    let mut imports = ImportSection::new();
+    for (i, (name, params, returns)) in signatures.iter().enumerate() {
+        let results: &[ValType] = if *returns { &[ValType::I32] } else { &[] };
+        types.function(vec![ValType::I32; *params], results.to_vec());
+        imports.import("reso", name, EntityType::Function(i as u32));
+    }
+    let validate_type = signatures.len() as u32;
+    types.function([], []);
+
+    let mut functions = FunctionSection::new();
+    functions.function(validate_type);
+
+    let mut exports = ExportSection::new();
+    exports.export("validate", ExportKind::Func, signatures.len() as u32);
+
+    let mut memories = MemorySection::new();
+    let mut data = DataSection::new();
+    if let Some(memory) = &input.memory {
+        let pages = usize::from(memory.pages % 3);
+        memories.memory(MemoryType {
+            minimum: pages as u64,
+            maximum: None,
+            memory64: false,
+            shared: false,
+        });
+        exports.export("memory", ExportKind::Memory, 0);
+        // Segments that don't fit would fail instantiation, which isn't what we're testing.
+        for (offset, bytes) in &memory.segments {
+            if usize::from(*offset) + bytes.len() <= pages * PAGE_SIZE {
+                data.active(0, &ConstExpr::i32_const(i32::from(*offset)), bytes.clone());
+            }
+        }
+    }
+
+    let mut validate = Function::new([]);
+    for call in &input.calls {
+        let function = usize::from(call.function) % signatures.len();
+        let (_, params, returns) = signatures[function];
+        for arg in &call.args[..params] {
+            validate.instruction(&Instruction::I32Const(arg.value()));
+        }
+        validate.instruction(&Instruction::Call(function as u32));
+        if returns {
+            validate.instruction(&Instruction::Drop);
+        }
+    }
+    validate.instruction(&Instruction::End);
+    let mut code = CodeSection::new();
+    code.function(&validate);
+
+    let mut module = Module::new();
+    module
+        .section(&types)
+        .section(&imports)
+        .section(&functions)
+        .section(&memories)
+        .section(&exports)
+        .section(&code)
+        .section(&data);
+    module.finish()
+}
+
+fuzz_target!(|input: Input| {
+    let module = build(&input);
+    for runtime in [Runtime::Wasmtime, Runtime::Wasmi] {
+        let validator = Validator::from_bytes(runtime, &module, false)
+            .unwrap_or_else(|err| panic!("{runtime} rejected a generated module: {err}"));
+        let context = Context::new(input.data.clone(), input.previous_data.clone(), 0);
+        if let Err(err) = validator.run(context) {
+            panic!("{runtime} neither trapped nor finished: {err}");
+        }
+    }
+});
@@ -0,0 +1,61 @@
+//! Read from a module's memory with arbitrary pointers and lengths, and check that every read
+//! either fails cleanly or returns exactly the bytes asked for.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use webassembly_rules_poc::memory::GuestSlice;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    memory: Vec<u8>,
+    len: i32,
+    ptr: i32,
+    contents: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let Input {
+        mut memory,
+        len,
+        ptr,
+        contents,
+    } = input;
+    // Pointers are unsigned to WebAssembly
+    let start = ptr as u32 as usize;
+    let expected = usize::try_from(len)
+        .ok()
+        .and_then(|len| memory.get(start..start.checked_add(len)?))
+        .map(<[u8]>::to_vec);
+
+    let Ok(slice) = GuestSlice::new(len, ptr, "fuzz") else {
+        assert!(expected.is_none());
+        return;
+    };
+    assert_eq!(slice.bytes(&memory).ok().map(<[u8]>::to_vec), expected);
+    if let Ok(string) = slice.string(&memory) {
+        assert_eq!(Some(string.as_bytes()), expected.as_deref());
+    }
+    assert_eq!(slice.string_lax(&memory).is_ok(), expected.is_some());
+    assert_eq!(
+        slice
+            .bytes_mut(&mut memory)
+            .ok()
+            .map(|slice| slice.to_vec()),
+        expected
+    );
+
+    let before = memory.clone();
+    match slice.write_if_fits(&mut memory, &contents) {
+        Ok((len, true)) => {
+            assert_eq!(len as usize, contents.len());
+            assert_eq!(&memory[start..start + contents.len()], &contents[..]);
+        }
+        Ok((len, false)) => {
+            assert_eq!(len as usize, contents.len());
+            assert_eq!(memory, before);
+        }
+        Err(_) => assert!(expected.is_none()),
+    }
+});
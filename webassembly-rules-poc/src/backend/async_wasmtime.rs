@@ -0,0 +1,175 @@
+use super::{
+    finish,
+    wasmtime::{coverage_counters, memory_and_context, Wasmtime},
+    Timings,
+};
+use crate::{
+    host::{self, host_functions},
+    Context, Error, Finished,
+};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often a running module yields to let other tasks on the runtime make progress
+const YIELD_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A module compiled by wasmtime, for running many validations concurrently on an async runtime
+/// such as tokio
+///
+/// A running module yields back to the runtime every [`YIELD_INTERVAL`], so a long validation
+/// never ties up a worker thread. Dropping the future returned by [`AsyncValidator::validate`],
+/// for example because a `tokio::time::timeout` ran out, stops the module at its next yield.
+pub struct AsyncValidator {
+    wasmtime: Wasmtime,
+    /// Tells the thread that advances the engine's epoch to stop, once the validator is dropped
+    stopped: Arc<AtomicBool>,
+}
+
+impl AsyncValidator {
+    /// Compile the module in the given file
+    ///
+    /// `measure` turns on fuel consumption, so that contexts that are collecting statistics can
+    /// report how many instructions were executed.
+    pub fn from_file(webassembly: &Path, measure: bool) -> Result<Self, Error> {
+        Self::new(measure, |config| Wasmtime::with_config(config, webassembly))
+    }
+
+    /// Compile a module that was already read, such as one whose signature was just verified,
+    /// naming `path` in any error
+    pub fn from_contents(path: &Path, contents: &[u8], measure: bool) -> Result<Self, Error> {
+        Self::new(measure, |config| {
+            Wasmtime::compile(config, path, |engine| {
+                wasmtime::Module::new(engine, contents)
+            })
+        })
+    }
+
+    fn new(
+        measure: bool,
+        compile: impl FnOnce(&wasmtime::Config) -> Result<Wasmtime, Error>,
+    ) -> Result<Self, Error> {
+        let mut config = wasmtime::Config::new();
+        config
+            .async_support(true)
+            .epoch_interruption(true)
+            .consume_fuel(measure);
+        let wasmtime = compile(&config)?;
+
+        // Running modules yield whenever the engine's epoch advances. That has to happen on a
+        // thread of its own: a task on the runtime would never get to run while a module hogged
+        // the only thread of a single-threaded runtime.
+        let engine = wasmtime.engine.clone();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let ticker_stopped = stopped.clone();
+        thread::spawn(move || {
+            while !ticker_stopped.load(Ordering::Relaxed) {
+                thread::sleep(YIELD_INTERVAL);
+                engine.increment_epoch();
+            }
+        });
+
+        Ok(Self { wasmtime, stopped })
+    }
+
+    /// Instantiate the module in a fresh store and call its `validate` function
+    pub async fn validate(&self, context: Context) -> Result<Finished, Error> {
+        let Wasmtime {
+            engine,
+            module,
+            compile_time,
+        } = &self.wasmtime;
+
+        let instantiate_started = Instant::now();
+        let mut store = wasmtime::Store::new(engine, context);
+        store.epoch_deadline_async_yield_and_update(1);
+        if store.data().stats.is_some() {
+            store.limiter(|context| context.stats.as_mut().unwrap());
+            // Only fails if the engine wasn't configured to consume fuel, in which case there's
+            // nothing to measure anyway.
+            let _ = store.add_fuel(u64::MAX);
+        }
+        let linker = create_linker(engine, module);
+        let instance = linker
+            .instantiate_async(&mut store, module)
+            .await
+            .map_err(Error::Instantiate)?;
+        let instantiate_time = instantiate_started.elapsed();
+
+        let function = instance
+            .get_typed_func::<(), ()>(&mut store, "validate")
+            .map_err(Error::MissingValidate)?;
+
+        store.data_mut().started = Instant::now();
+        let trap = function.call_async(&mut store, ()).await.err();
+        let timings = Timings {
+            compile: *compile_time,
+            instantiate: instantiate_time,
+            validate: store.data().started.elapsed(),
+            fuel_consumed: store.fuel_consumed(),
+        };
+
+        let coverage = coverage_counters(&mut store, &instance);
+
+        Ok(finish(store.into_data(), trap, timings, coverage))
+    }
+}
+
+impl Drop for AsyncValidator {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Define all of the host functions that the module can call, as async functions
+///
+/// The host functions themselves don't wait on anything yet, but linking them this way means one
+/// that needs to, such as a lookup against a remote service, can await without blocking the runtime.
+fn create_linker(
+    engine: &wasmtime::Engine,
+    module: &wasmtime::Module,
+) -> wasmtime::Linker<Context> {
+    let mut linker = wasmtime::Linker::new(engine);
+
+    // wasmtime has a separate `func_wrapN_async` for each number of arguments.
+    macro_rules! define {
+        (@wrap $wrap:ident, $name:ident($($arg:ident),*)) => {
+            linker
+                .$wrap(
+                    "reso",
+                    stringify!($name),
+                    |mut caller: wasmtime::Caller<'_, Context>, $($arg: i32),*| {
+                        Box::new(async move {
+                            let (memory, context) = memory_and_context(&mut caller)?;
+                            host::$name(memory, context, $($arg),*)
+                        })
+                    },
+                )
+                .unwrap();
+        };
+        ($name:ident($a:ident, $b:ident) -> $result:ty) => {
+            define!(@wrap func_wrap2_async, $name($a, $b))
+        };
+        ($name:ident($a:ident, $b:ident, $c:ident) -> $result:ty) => {
+            define!(@wrap func_wrap3_async, $name($a, $b, $c))
+        };
+        ($name:ident($a:ident, $b:ident, $c:ident, $d:ident) -> $result:ty) => {
+            define!(@wrap func_wrap4_async, $name($a, $b, $c, $d))
+        };
+        ($name:ident($a:ident, $b:ident, $c:ident, $d:ident, $e:ident, $f:ident) -> $result:ty) => {
+            define!(@wrap func_wrap6_async, $name($a, $b, $c, $d, $e, $f))
+        };
+    }
+    host_functions!(define);
+
+    // Any other import is allowed, but traps if it's ever called, as with the blocking linker.
+    linker.define_unknown_imports_as_traps(module).unwrap();
+
+    linker
+}
@@ -0,0 +1,125 @@
+//! The WebAssembly runtimes a module can be run with
+//!
+//! Host functions are written once, in [`crate::host`], against a plain slice of the module's
+//! memory. Each backend compiles modules with its own runtime and links those functions in.
+
+use crate::{host::log_call, Context, Error, Finished};
+use std::{collections::BTreeMap, fmt, path::Path, time::Duration};
+
+#[cfg(feature = "async")]
+mod async_wasmtime;
+#[cfg(feature = "wasmi")]
+mod wasmi;
+mod wasmtime;
+
+#[cfg(feature = "async")]
+pub use async_wasmtime::AsyncValidator;
+
+/// What errors call a module that was compiled from memory rather than read from a file
+const IN_MEMORY: &str = "(in memory)";
+
+/// A WebAssembly runtime a module can be run with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Runtime {
+    /// wasmtime, which compiles modules to native code before running them
+    #[default]
+    Wasmtime,
+    /// wasmi, a pure interpreter for targets that forbid generating code at runtime
+    #[cfg(feature = "wasmi")]
+    Wasmi,
+}
+
+impl Runtime {
+    /// Compile the module in the given file with this runtime
+    ///
+    /// `measure` turns on fuel consumption, so that contexts that are collecting statistics can
+    /// report how many instructions were executed.
+    pub fn compile(self, webassembly: &Path, measure: bool) -> Result<Box<dyn Backend>, Error> {
+        Ok(match self {
+            Runtime::Wasmtime => {
+                Box::new(self::wasmtime::Wasmtime::from_file(webassembly, measure)?)
+            }
+            #[cfg(feature = "wasmi")]
+            Runtime::Wasmi => Box::new(self::wasmi::Wasmi::from_file(webassembly, measure)?),
+        })
+    }
+}
+
+impl Runtime {
+    /// Compile a module that's already in memory, such as one built by a fuzzer
+    pub fn compile_bytes(
+        self,
+        webassembly: &[u8],
+        measure: bool,
+    ) -> Result<Box<dyn Backend>, Error> {
+        self.compile_contents(Path::new(IN_MEMORY), webassembly, measure)
+    }
+
+    /// Compile the contents of a module that were already read from a file
+    ///
+    /// The path is only used to describe errors.
+    pub fn compile_contents(
+        self,
+        path: &Path,
+        contents: &[u8],
+        measure: bool,
+    ) -> Result<Box<dyn Backend>, Error> {
+        Ok(match self {
+            Runtime::Wasmtime => Box::new(self::wasmtime::Wasmtime::from_contents(
+                path, contents, measure,
+            )?),
+            #[cfg(feature = "wasmi")]
+            Runtime::Wasmi => Box::new(self::wasmi::Wasmi::from_contents(path, contents, measure)?),
+        })
+    }
+}
+
+impl fmt::Display for Runtime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Runtime::Wasmtime => write!(f, "wasmtime"),
+            #[cfg(feature = "wasmi")]
+            Runtime::Wasmi => write!(f, "wasmi"),
+        }
+    }
+}
+
+/// A module compiled by one of the runtimes, ready to be run against any number of listings
+pub trait Backend {
+    /// The runtime that compiled the module
+    fn runtime(&self) -> Runtime;
+
+    /// Instantiate the module in a fresh store and call its `validate` function
+    fn run(&self, context: Context) -> Result<Finished, Error>;
+}
+
+/// How long each step of a run took, and how much work `validate` did
+struct Timings {
+    compile: Duration,
+    instantiate: Duration,
+    validate: Duration,
+    fuel_consumed: Option<u64>,
+}
+
+/// Wrap up a run the same way whichever runtime did it
+fn finish(
+    mut context: Context,
+    trap: Option<anyhow::Error>,
+    timings: Timings,
+    coverage: BTreeMap<String, u64>,
+) -> Finished {
+    if let Some(stats) = &mut context.stats {
+        stats.compile_us = timings.compile.as_micros() as u64;
+        stats.instantiate_us = timings.instantiate.as_micros() as u64;
+        stats.validate_us = timings.validate.as_micros() as u64;
+        stats.fuel_consumed = timings.fuel_consumed;
+    }
+    if trap.is_none() {
+        log_call!(context, "Validation program finished");
+    }
+    Finished {
+        context,
+        trap,
+        coverage,
+    }
+}
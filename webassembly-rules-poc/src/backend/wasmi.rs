@@ -0,0 +1,175 @@
+use super::{finish, Backend, Runtime, Timings};
+use crate::{
+    coverage,
+    host::{self, host_functions},
+    Context, Error, Finished,
+};
+use std::{collections::BTreeMap, path::Path, time::Instant};
+use wasmi::core::Trap;
+
+/// A module run by the wasmi interpreter, which never generates code at runtime
+pub struct Wasmi {
+    engine: wasmi::Engine,
+    module: wasmi::Module,
+    compile_time: std::time::Duration,
+}
+
+impl Wasmi {
+    pub fn from_file(webassembly: &Path, measure: bool) -> Result<Self, Error> {
+        let contents = std::fs::read(webassembly).map_err(|source| Error::ReadInput {
+            path: webassembly.to_path_buf(),
+            source,
+        })?;
+        Self::compile(&contents, webassembly, measure)
+    }
+
+    pub fn from_contents(path: &Path, contents: &[u8], measure: bool) -> Result<Self, Error> {
+        Self::compile(contents, path, measure)
+    }
+
+    fn compile(contents: &[u8], path: &Path, measure: bool) -> Result<Self, Error> {
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(measure);
+        let engine = wasmi::Engine::new(&config);
+
+        let compile_error = |source| Error::Compile {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        // Like wasmtime, accept the text format as well as binary modules.
+        let compile_started = Instant::now();
+        let binary = wat::parse_bytes(contents).map_err(|err| compile_error(err.into()))?;
+        let module = wasmi::Module::new(&engine, &binary[..])
+            .map_err(|err| compile_error(anyhow::anyhow!("{err}")))?;
+
+        Ok(Self {
+            engine,
+            module,
+            compile_time: compile_started.elapsed(),
+        })
+    }
+}
+
+impl Backend for Wasmi {
+    fn runtime(&self) -> Runtime {
+        Runtime::Wasmi
+    }
+
+    fn run(&self, context: Context) -> Result<Finished, Error> {
+        let instantiate_started = Instant::now();
+        let mut store = wasmi::Store::new(&self.engine, context);
+        if store.data().stats.is_some() {
+            store.limiter(|context| context.stats.as_mut().unwrap());
+            // Only fails if the engine wasn't configured to consume fuel, in which case there's
+            // nothing to measure anyway.
+            let _ = store.add_fuel(u64::MAX);
+        }
+        let linker = create_linker(&mut store, &self.module);
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|err| Error::Instantiate(anyhow::anyhow!("{err}")))?;
+        let instantiate_time = instantiate_started.elapsed();
+
+        let function = instance
+            .get_typed_func::<(), ()>(&store, "validate")
+            .map_err(|err| Error::MissingValidate(anyhow::anyhow!("{err}")))?;
+
+        store.data_mut().started = Instant::now();
+        let trap = function
+            .call(&mut store, ())
+            .err()
+            .map(|err| anyhow::anyhow!("{err}"));
+        let timings = Timings {
+            compile: self.compile_time,
+            instantiate: instantiate_time,
+            validate: store.data().started.elapsed(),
+            fuel_consumed: store.fuel_consumed(),
+        };
+
+        let coverage = coverage_counters(&store, &instance);
+
+        Ok(finish(store.into_data(), trap, timings, coverage))
+    }
+}
+
+/// Get the module's exported memory, alongside the context
+fn memory_and_context<'a>(
+    caller: &'a mut wasmi::Caller<'_, Context>,
+) -> Result<(&'a mut [u8], &'a mut Context), Trap> {
+    let Some(memory) = caller
+        .get_export("memory")
+        .and_then(|memory| memory.into_memory())
+    else {
+        return Err(Trap::new("No memory export"));
+    };
+
+    Ok(memory.data_and_store_mut(caller))
+}
+
+/// Read the coverage counters the module exports, once `validate` has run
+fn coverage_counters(
+    store: &wasmi::Store<Context>,
+    instance: &wasmi::Instance,
+) -> BTreeMap<String, u64> {
+    instance
+        .exports(store)
+        .filter_map(|export| {
+            let name = export.name().strip_prefix(coverage::PREFIX)?.to_string();
+            match export.into_global()?.get(store) {
+                wasmi::Value::I64(count) => Some((name, count as u64)),
+                wasmi::Value::I32(count) => Some((name, count as u32 as u64)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Define all of the host functions that the module can call
+///
+/// Unlike wasmtime's, a wasmi linker can only hold functions for a single store.
+fn create_linker(
+    store: &mut wasmi::Store<Context>,
+    module: &wasmi::Module,
+) -> wasmi::Linker<Context> {
+    let mut linker = wasmi::Linker::new(store.engine());
+    let mut defined = Vec::new();
+
+    macro_rules! define {
+        ($name:ident($($arg:ident),*) -> $result:ty) => {
+            linker
+                .func_wrap(
+                    "reso",
+                    stringify!($name),
+                    |mut caller: wasmi::Caller<'_, Context>,
+                     $($arg: i32),*|
+                     -> Result<$result, Trap> {
+                        let (memory, context) = memory_and_context(&mut caller)?;
+                        host::$name(memory, context, $($arg),*)
+                            .map_err(|err| Trap::new(format!("{err:#}")))
+                    },
+                )
+                .unwrap();
+            defined.push(stringify!($name));
+        };
+    }
+    host_functions!(define);
+
+    // As with wasmtime, any other function import is allowed, but traps if it's ever called.
+    for import in module.imports() {
+        let wasmi::ExternType::Func(ty) = import.ty() else {
+            continue;
+        };
+        if import.module() == "reso" && defined.contains(&import.name()) {
+            continue;
+        }
+        let message = format!("{}::{} is not implemented", import.module(), import.name());
+        let func = wasmi::Func::new(&mut *store, ty.clone(), move |_, _, _| {
+            Err(Trap::new(message.clone()))
+        });
+        linker.define(import.module(), import.name(), func).unwrap();
+    }
+
+    linker
+}
@@ -0,0 +1,176 @@
+use super::{finish, Backend, Runtime, Timings};
+use crate::{
+    coverage,
+    host::{self, host_functions},
+    Context, Error, Finished,
+};
+use std::{collections::BTreeMap, path::Path, time::Instant};
+
+/// A module compiled to native code by wasmtime
+pub struct Wasmtime {
+    pub(super) engine: wasmtime::Engine,
+    pub(super) module: wasmtime::Module,
+    pub(super) compile_time: std::time::Duration,
+}
+
+impl Wasmtime {
+    pub fn from_file(webassembly: &Path, measure: bool) -> Result<Self, Error> {
+        // Base wasmtime code we need for a new engine. Fuel is only needed to count instructions
+        // for statistics.
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(measure);
+        Self::with_config(&config, webassembly)
+    }
+
+    pub fn from_contents(path: &Path, contents: &[u8], measure: bool) -> Result<Self, Error> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(measure);
+        Self::compile(&config, path, |engine| {
+            wasmtime::Module::new(engine, contents)
+        })
+    }
+
+    /// Compile the module with an engine configured by the caller
+    pub(super) fn with_config(
+        config: &wasmtime::Config,
+        webassembly: &Path,
+    ) -> Result<Self, Error> {
+        Self::compile(config, webassembly, |engine| {
+            wasmtime::Module::from_file(engine, webassembly)
+        })
+    }
+
+    /// Compile a module with an engine configured by the caller, naming `path` in any error
+    pub(super) fn compile(
+        config: &wasmtime::Config,
+        path: &Path,
+        compile: impl FnOnce(&wasmtime::Engine) -> anyhow::Result<wasmtime::Module>,
+    ) -> Result<Self, Error> {
+        let engine = wasmtime::Engine::new(config).map_err(Error::Engine)?;
+
+        // Parse the module from the passed in webassembly.
+        let compile_started = Instant::now();
+        let module = compile(&engine).map_err(|source| Error::Compile {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Ok(Self {
+            engine,
+            module,
+            compile_time: compile_started.elapsed(),
+        })
+    }
+}
+
+impl Backend for Wasmtime {
+    fn runtime(&self) -> Runtime {
+        Runtime::Wasmtime
+    }
+
+    fn run(&self, context: Context) -> Result<Finished, Error> {
+        // Instantiate a new instance.
+        let instantiate_started = Instant::now();
+        let mut store = wasmtime::Store::new(&self.engine, context);
+        if store.data().stats.is_some() {
+            store.limiter(|context| context.stats.as_mut().unwrap());
+            // Only fails if the engine wasn't configured to consume fuel, in which case there's
+            // nothing to measure anyway.
+            let _ = store.add_fuel(u64::MAX);
+        }
+        let linker = create_linker(&self.engine, &self.module);
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .map_err(Error::Instantiate)?;
+        let instantiate_time = instantiate_started.elapsed();
+
+        // Find the validate function in the module.
+        let function = instance
+            .get_typed_func::<(), ()>(&mut store, "validate")
+            .map_err(Error::MissingValidate)?;
+
+        // And call the validate function!
+        store.data_mut().started = Instant::now();
+        let trap = function.call(&mut store, ()).err();
+        let timings = Timings {
+            compile: self.compile_time,
+            instantiate: instantiate_time,
+            validate: store.data().started.elapsed(),
+            fuel_consumed: store.fuel_consumed(),
+        };
+
+        let coverage = coverage_counters(&mut store, &instance);
+
+        // That's it. We're done.
+        Ok(finish(store.into_data(), trap, timings, coverage))
+    }
+}
+
+/// Read the coverage counters the module exports, once `validate` has run
+pub(super) fn coverage_counters(
+    store: &mut wasmtime::Store<Context>,
+    instance: &wasmtime::Instance,
+) -> BTreeMap<String, u64> {
+    let globals: Vec<_> = instance
+        .exports(&mut *store)
+        .filter_map(|export| {
+            let name = export.name().strip_prefix(coverage::PREFIX)?.to_string();
+            Some((name, export.into_global()?))
+        })
+        .collect();
+    globals
+        .into_iter()
+        .filter_map(|(name, global)| match global.get(&mut *store) {
+            wasmtime::Val::I64(count) => Some((name, count as u64)),
+            wasmtime::Val::I32(count) => Some((name, count as u32 as u64)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Get the module's exported memory, alongside the context
+pub(super) fn memory_and_context<'a>(
+    caller: &'a mut wasmtime::Caller<'_, Context>,
+) -> wasmtime::Result<(&'a mut [u8], &'a mut Context)> {
+    let Some(memory) = caller
+        .get_export("memory")
+        .and_then(|memory| memory.into_memory())
+    else {
+        anyhow::bail!("No memory export");
+    };
+
+    Ok(memory.data_and_store_mut(caller))
+}
+
+/// Define all of the host functions that the module can call
+fn create_linker(
+    engine: &wasmtime::Engine,
+    module: &wasmtime::Module,
+) -> wasmtime::Linker<Context> {
+    let mut linker = wasmtime::Linker::new(engine);
+
+    macro_rules! define {
+        ($name:ident($($arg:ident),*) -> $result:ty) => {
+            linker
+                .func_wrap(
+                    "reso",
+                    stringify!($name),
+                    |mut caller: wasmtime::Caller<'_, Context>,
+                     $($arg: i32),*|
+                     -> wasmtime::Result<$result> {
+                        let (memory, context) = memory_and_context(&mut caller)?;
+                        host::$name(memory, context, $($arg),*)
+                    },
+                )
+                .unwrap();
+        };
+    }
+    host_functions!(define);
+
+    // Any other import is allowed, but won't do anything useful. This is required because some
+    // languages implicitly assume that wasm is compiled as wasi, and provide imports for wasi, even
+    // if the module never calls them.
+    linker.define_unknown_imports_as_traps(module).unwrap();
+
+    linker
+}
@@ -0,0 +1,328 @@
+use crate::metadata::Metadata;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+use walrus::{ir::BinaryOp, ExportItem, InitExpr, ValType};
+
+/// Exported globals whose names start with this are coverage counters, read after `validate`
+///
+/// `reso.coverage.function.<name>` counts how many times a function ran, and is added by
+/// [`instrument`]. `reso.coverage.rule.<index>.hit` and `.fired` count how many times a rule was
+/// evaluated and how many times it took its action, and are exported by generators that know
+/// about rules, such as rcp19-to-wasm.
+pub const PREFIX: &str = "reso.coverage.";
+
+/// Rewrite a module so that it counts how many times each of its functions runs
+///
+/// Every function the module defines gets a mutable `i64` global that's bumped as the function is
+/// entered, exported as `reso.coverage.function.<name>`. Functions are named from the module's
+/// name section, falling back to the name they're exported under and then their index.
+pub fn instrument(webassembly: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut module = walrus::Module::from_buffer(webassembly)?;
+
+    let exported: BTreeMap<_, _> = module
+        .exports
+        .iter()
+        .filter_map(|export| match export.item {
+            ExportItem::Function(id) => Some((id, export.name.clone())),
+            _ => None,
+        })
+        .collect();
+    let functions: Vec<_> = module.funcs.iter_local().map(|(id, _)| id).collect();
+    let mut names = BTreeSet::new();
+    for id in functions {
+        let function = module.funcs.get(id);
+        // Rust names are demangled, without their hashes, so reports are readable.
+        let mut name = match (&function.name, exported.get(&id)) {
+            (Some(name), _) => format!("{:#}", rustc_demangle::demangle(name)),
+            (None, Some(export)) => export.clone(),
+            (None, None) => id.index().to_string(),
+        };
+        // Export names have to be unique, and name sections don't promise that.
+        if !names.insert(name.clone()) {
+            name = format!("{name}#{}", id.index());
+            names.insert(name.clone());
+        }
+
+        let counter = module.globals.add_local(
+            ValType::I64,
+            true,
+            InitExpr::Value(walrus::ir::Value::I64(0)),
+        );
+        module
+            .exports
+            .add(&format!("{PREFIX}function.{name}"), counter);
+
+        let function = module.funcs.get_mut(id).kind.unwrap_local_mut();
+        function
+            .builder_mut()
+            .func_body()
+            .global_get_at(0, counter)
+            .const_at(1, walrus::ir::Value::I64(1))
+            .binop_at(2, BinaryOp::I64Add)
+            .global_set_at(3, counter);
+    }
+
+    Ok(module.emit_wasm())
+}
+
+/// Coverage counters added up over a batch of runs
+#[derive(Debug, Default, Serialize)]
+pub struct Coverage {
+    /// How many runs were counted
+    pub runs: u64,
+    /// How many times each function ran, keyed by name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub functions: BTreeMap<String, u64>,
+    /// How often each rule was evaluated and took its action, keyed by index, for modules that
+    /// count it
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub rules: BTreeMap<usize, RuleCoverage>,
+    /// Whether the module counts how often its rules run, if its metadata lists any rules or it
+    /// has rule counters. A module built from a template without counters only gets function
+    /// coverage, rather than every rule showing up as never hit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules_instrumented: Option<bool>,
+    /// What each rule is, by index, from the module's metadata
+    #[serde(skip)]
+    descriptions: Vec<String>,
+}
+
+/// Counts for a single rule
+#[derive(Debug, Default, Serialize)]
+pub struct RuleCoverage {
+    /// What the rule is, if the module's metadata said
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// How many times the rule was evaluated, if the module counts that
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hit: Option<u64>,
+    /// How many times the rule took its action, such as reporting an error, if the module counts
+    /// that
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fired: Option<u64>,
+}
+
+impl Coverage {
+    /// Start counting, describing rules with the module's metadata if it has any
+    pub fn new(metadata: Option<&Metadata>) -> Self {
+        let descriptions: Vec<_> = metadata.map(|m| m.rules.clone()).unwrap_or_default();
+        Self {
+            rules_instrumented: (!descriptions.is_empty()).then_some(false),
+            descriptions,
+            ..Self::default()
+        }
+    }
+
+    /// Add the counters from a single run, keyed by their names without the `reso.coverage.`
+    /// prefix
+    pub fn record(&mut self, counters: &BTreeMap<String, u64>) {
+        self.runs += 1;
+        for (name, count) in counters {
+            if let Some(function) = name.strip_prefix("function.") {
+                *self.functions.entry(function.to_string()).or_default() += count;
+                continue;
+            }
+            let Some((index, counter)) = name
+                .strip_prefix("rule.")
+                .and_then(|rule| rule.split_once('.'))
+            else {
+                continue;
+            };
+            let Ok(index) = index.parse() else {
+                continue;
+            };
+            if counter != "hit" && counter != "fired" {
+                continue;
+            }
+            self.rules_instrumented = Some(true);
+            let rule = self.rules.entry(index).or_insert_with(|| RuleCoverage {
+                description: self.descriptions.get(index).cloned(),
+                ..RuleCoverage::default()
+            });
+            let counted = match counter {
+                "hit" => &mut rule.hit,
+                _ => &mut rule.fired,
+            };
+            *counted.get_or_insert(0) += count;
+        }
+    }
+}
+
+/// The coverage in a form meant for people
+///
+/// Every rule is listed, but only functions that never ran are, since modules compiled from
+/// languages like Rust or Go have a great many functions.
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// How many functions that never ran to name before summarizing the rest
+        const UNRUN_SHOWN: usize = 20;
+
+        writeln!(
+            f,
+            "🧪 Coverage over {} run{}",
+            self.runs,
+            if self.runs == 1 { "" } else { "s" }
+        )?;
+        if self.rules_instrumented == Some(false) {
+            writeln!(
+                f,
+                "   rules      not instrumented, so only functions are shown; rebuild the module \
+                 with a current rcp19-to-wasm to count its {} rules",
+                self.descriptions.len()
+            )?;
+        }
+        if !self.rules.is_empty() {
+            let total = self.rules.len();
+            let counted = |counter: fn(&RuleCoverage) -> Option<u64>, verb| {
+                let counts: Vec<_> = self.rules.values().map(counter).collect();
+                if counts.iter().all(Option::is_none) {
+                    return None;
+                }
+                let counted = counts.iter().filter(|count| **count > Some(0)).count();
+                Some(format!("{counted} of {total} {verb}"))
+            };
+            let summary: Vec<_> = [
+                counted(|rule| rule.hit, "hit"),
+                counted(|rule| rule.fired, "fired"),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            writeln!(f, "   rules      {}", summary.join(", "))?;
+            for (index, rule) in &self.rules {
+                write!(
+                    f,
+                    "   {:<10} {:>7} hit {:>7} fired",
+                    format!("rule {index}"),
+                    count(rule.hit),
+                    count(rule.fired),
+                )?;
+                match &rule.description {
+                    Some(description) => writeln!(f, "  {description}")?,
+                    None => writeln!(f)?,
+                }
+            }
+        }
+        if !self.functions.is_empty() {
+            let unrun: Vec<_> = self
+                .functions
+                .iter()
+                .filter(|(_, count)| **count == 0)
+                .map(|(name, _)| name)
+                .collect();
+            let total = self.functions.len();
+            let ran = total - unrun.len();
+            writeln!(
+                f,
+                "   functions  {ran} of {total} ran ({}%)",
+                ran * 100 / total.max(1)
+            )?;
+            for name in unrun.iter().take(UNRUN_SHOWN) {
+                writeln!(f, "   never ran  {name}")?;
+            }
+            if unrun.len() > UNRUN_SHOWN {
+                writeln!(f, "   ... and {} more", unrun.len() - UNRUN_SHOWN)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A count for the human report, or a dash if the module doesn't keep it
+fn count(count: Option<u64>) -> String {
+    count.map_or_else(|| "-".to_string(), |count| count.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Metadata {
+        Metadata {
+            rules: vec![
+                "ListPrice REJECT: too low".into(),
+                "City WARN: missing".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+
+    fn counters(counters: &[(&str, u64)]) -> BTreeMap<String, u64> {
+        counters
+            .iter()
+            .map(|(name, count)| (name.to_string(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn counters_are_added_up_across_runs() {
+        let mut coverage = Coverage::new(Some(&metadata()));
+        let run = counters(&[
+            ("rule.0.hit", 1),
+            ("rule.0.fired", 0),
+            ("rule.1.hit", 2),
+            ("rule.1.fired", 1),
+            ("function.validate", 1),
+        ]);
+        coverage.record(&run);
+        coverage.record(&run);
+
+        assert_eq!(coverage.runs, 2);
+        assert_eq!(coverage.rules_instrumented, Some(true));
+        assert_eq!(coverage.functions["validate"], 2);
+        assert_eq!(coverage.rules[&0].hit, Some(2));
+        assert_eq!(coverage.rules[&0].fired, Some(0));
+        assert_eq!(coverage.rules[&1].fired, Some(2));
+        assert_eq!(
+            coverage.rules[&1].description.as_deref(),
+            Some("City WARN: missing")
+        );
+
+        let report = coverage.to_string();
+        assert!(
+            report.contains("rules      2 of 2 hit, 1 of 2 fired"),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn rules_without_counters_are_not_reported_as_never_hit() {
+        let mut coverage = Coverage::new(Some(&metadata()));
+        coverage.record(&counters(&[("function.validate", 1)]));
+
+        assert_eq!(coverage.rules_instrumented, Some(false));
+        assert!(coverage.rules.is_empty());
+        let json = serde_json::to_value(&coverage).unwrap();
+        assert_eq!(json["rules_instrumented"], false);
+        assert!(json.get("rules").is_none());
+
+        let report = coverage.to_string();
+        assert!(report.contains("not instrumented"), "{report}");
+        assert!(report.contains("functions  1 of 1 ran"), "{report}");
+        assert!(!report.contains("0 hit"), "{report}");
+    }
+
+    #[test]
+    fn counters_the_module_does_not_keep_are_left_out() {
+        let mut coverage = Coverage::new(None);
+        coverage.record(&counters(&[("rule.0.hit", 3)]));
+
+        assert_eq!(coverage.rules[&0].hit, Some(3));
+        assert_eq!(coverage.rules[&0].fired, None);
+        let report = coverage.to_string();
+        assert!(report.contains("rules      1 of 1 hit\n"), "{report}");
+        assert!(report.contains("- fired"), "{report}");
+    }
+
+    #[test]
+    fn modules_without_rules_say_nothing_about_them() {
+        let mut coverage = Coverage::new(None);
+        coverage.record(&counters(&[("function.validate", 0)]));
+
+        assert_eq!(coverage.rules_instrumented, None);
+        assert!(!coverage.to_string().contains("rules"));
+    }
+}
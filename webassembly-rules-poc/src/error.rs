@@ -0,0 +1,124 @@
+use crate::{schema::Mismatch, signature::Problem};
+use std::{fmt, path::PathBuf};
+
+/// Everything that can stop a module from producing an outcome
+#[derive(Debug)]
+pub enum Error {
+    /// An input file couldn't be read
+    ReadInput {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// An input file was read, but wasn't what we expected
+    ParseInput {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// A page of listings couldn't be fetched from a server
+    Fetch { url: String, source: std::io::Error },
+    /// An output file couldn't be written
+    WriteOutput {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The options can't be used with the inputs given, such as recording a trace of a batch
+    Usage(String),
+    /// An input's fields don't have the types the schema gives them, so the module wasn't run
+    Schema {
+        path: PathBuf,
+        mismatches: Vec<Mismatch>,
+    },
+    /// The module's signature couldn't be verified against the trusted keys
+    Unverified { path: PathBuf, problem: Problem },
+    /// The runtime's engine couldn't be created
+    Engine(anyhow::Error),
+    /// The module couldn't be compiled
+    Compile {
+        path: PathBuf,
+        source: anyhow::Error,
+    },
+    /// The module couldn't be instantiated, usually because of a bad import
+    Instantiate(anyhow::Error),
+    /// The module doesn't export a `validate` function with the right signature
+    MissingValidate(anyhow::Error),
+    /// `validate` trapped
+    Trap(anyhow::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ReadInput { path, source } => {
+                write!(f, "Failed to read '{}': {source}", path.to_string_lossy())
+            }
+            Error::ParseInput { path, source } => {
+                write!(
+                    f,
+                    "Contents of '{}' was not valid: {source}",
+                    path.to_string_lossy()
+                )
+            }
+            Error::Fetch { url, source } => write!(f, "Failed to fetch '{url}': {source}"),
+            Error::WriteOutput { path, source } => {
+                write!(f, "Failed to write '{}': {source}", path.to_string_lossy())
+            }
+            Error::Usage(problem) => write!(f, "{problem}"),
+            Error::Schema { path, mismatches } => {
+                write!(
+                    f,
+                    "Contents of '{}' don't match the RESO Data Dictionary:",
+                    path.to_string_lossy()
+                )?;
+                for mismatch in mismatches {
+                    write!(f, "\n  {mismatch}")?;
+                }
+                Ok(())
+            }
+            Error::Unverified { path, problem } => {
+                write!(
+                    f,
+                    "'{}' could not be verified: {problem}",
+                    path.to_string_lossy()
+                )
+            }
+            Error::Engine(err) => write!(f, "Failed to create engine: {err}"),
+            Error::Compile { path, source } => {
+                write!(
+                    f,
+                    "Failed to create module from {}: {source}",
+                    path.to_string_lossy()
+                )
+            }
+            Error::Instantiate(err) => write!(f, "Failed to instantiate module: {err}"),
+            Error::MissingValidate(err) => write!(
+                f,
+                "Failed to get `validate` function from WebAssembly module: {err}"
+            ),
+            Error::Trap(err) => {
+                // Runtimes wrap the reason in a backtrace, which shouldn't bury it
+                write!(f, "Execution failed: {}", err.root_cause())?;
+                if err.chain().count() > 1 {
+                    write!(f, "\n{err}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ReadInput { source, .. } => Some(source),
+            Error::ParseInput { source, .. } => Some(source),
+            Error::Fetch { source, .. } => Some(source),
+            Error::WriteOutput { source, .. } => Some(source),
+            Error::Usage(_) | Error::Schema { .. } | Error::Unverified { .. } => None,
+            Error::Engine(err)
+            | Error::Compile { source: err, .. }
+            | Error::Instantiate(err)
+            | Error::MissingValidate(err)
+            | Error::Trap(err) => Some(err.as_ref()),
+        }
+    }
+}
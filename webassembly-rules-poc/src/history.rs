@@ -0,0 +1,134 @@
+use crate::{read_json, Error};
+use serde_json::Value;
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+/// A local store of the last accepted version of every listing, keyed by `ListingKey`
+///
+/// Used to supply `previous_data` automatically, so rules about changes work without the caller
+/// keeping track of earlier versions. Each listing is kept as its own JSON file in a directory.
+#[derive(Debug, Clone)]
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    /// Open the store in a directory, creating it if it doesn't exist yet
+    pub fn open(dir: &Path) -> Result<Self, Error> {
+        std::fs::create_dir_all(dir).map_err(|source| Error::WriteOutput {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// The last accepted version of a listing, if there is one
+    pub fn previous(&self, listing_key: &str) -> Result<Option<Value>, Error> {
+        let path = self.path(listing_key);
+        match path.exists() {
+            true => read_json(&path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Remember a listing as the last accepted version
+    ///
+    /// The file is written next to the old one and renamed over it, so an interrupted run never
+    /// leaves a half-written listing behind.
+    pub fn record(&self, listing_key: &str, listing: &Value) -> Result<(), Error> {
+        let path = self.path(listing_key);
+        let partial = path.with_extension("json.partial");
+        let write_error = |source| Error::WriteOutput {
+            path: path.clone(),
+            source,
+        };
+        let contents = serde_json::to_vec_pretty(listing).unwrap();
+        std::fs::write(&partial, contents).map_err(write_error)?;
+        std::fs::rename(&partial, &path).map_err(write_error)
+    }
+
+    /// Where a listing is kept
+    ///
+    /// Anything in the key that isn't safe in a file name is percent-encoded, so every key gets
+    /// its own file.
+    pub fn path(&self, listing_key: &str) -> PathBuf {
+        let mut name = String::with_capacity(listing_key.len() + 5);
+        for byte in listing_key.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => name.push(byte as char),
+                _ => write!(name, "%{byte:02X}").unwrap(),
+            }
+        }
+        name.push_str(".json");
+        self.dir.join(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::History;
+    use serde_json::json;
+
+    /// A history in a directory of its own, removed and recreated so it starts out empty
+    fn history(name: &str) -> History {
+        let dir = std::env::temp_dir().join(format!("history-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        History::open(&dir).unwrap()
+    }
+
+    #[test]
+    fn recorded_listings_come_back_as_the_previous_data() {
+        let history = history("round-trip");
+        assert_eq!(history.previous("1").unwrap(), None);
+
+        let first = json!({ "ListingKey": "1", "ListPrice": 1, "Media": [{ "Order": 1 }] });
+        history.record("1", &first).unwrap();
+        assert_eq!(history.previous("1").unwrap(), Some(first));
+
+        let second = json!({ "ListingKey": "1", "ListPrice": 2 });
+        history.record("1", &second).unwrap();
+        assert_eq!(history.previous("1").unwrap(), Some(second));
+        assert_eq!(history.previous("2").unwrap(), None);
+
+        // Nothing but the listing itself is left behind
+        let files: Vec<_> = std::fs::read_dir(&history.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["1.json"]);
+        std::fs::remove_dir_all(&history.dir).unwrap();
+    }
+
+    #[test]
+    fn every_key_gets_a_file_of_its_own() {
+        let history = history("keys");
+        let keys = ["a/b", "a%2Fb", "../a", "a.b", "a b", "ä"];
+        let names: Vec<_> = keys
+            .iter()
+            .map(|key| history.path(key).file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "a%2Fb.json",
+                "a%252Fb.json",
+                "%2E%2E%2Fa.json",
+                "a%2Eb.json",
+                "a%20b.json",
+                "%C3%A4.json",
+            ]
+        );
+
+        for (i, key) in keys.iter().enumerate() {
+            history.record(key, &json!(i)).unwrap();
+        }
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(history.previous(key).unwrap(), Some(json!(i)));
+        }
+        std::fs::remove_dir_all(&history.dir).unwrap();
+    }
+}
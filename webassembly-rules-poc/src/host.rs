@@ -0,0 +1,774 @@
+use crate::{
+    memory::GuestSlice,
+    messages::Catalog,
+    outcome::{Message, Outcome, Severity},
+    path::FieldPath,
+    quota::{Quotas, Usage},
+    stats::Stats,
+    trace::HostCall,
+};
+use std::{borrow::Cow, cell::OnceCell, collections::BTreeMap, time::Instant};
+
+/// Quick little helper that helps with logging host calls.
+macro_rules! log_call {
+    ($context:expr, $($rest:tt)*) => {
+        if $context.verbose >= 2 {
+            println!("\x1b[3;90m{}\x1b[0m", format_args!($($rest)*));
+        }
+    };
+}
+pub(crate) use log_call;
+
+pub struct Context {
+    /// Stringified version of the JSON for the data
+    pub data: String,
+    /// Stringified version of the JSON for the previous data
+    pub previous_data: String,
+    /// Stringified versions of the JSON for related resources (such as `Media` or `Rooms`), keyed
+    /// by resource name
+    pub related: BTreeMap<String, String>,
+    /// The fields the caller says were edited, which `reso.changed_fields` reports instead of
+    /// comparing the data with the previous data
+    pub edited: Option<Vec<String>>,
+    /// Which verbosity level we're at
+    pub verbose: u8,
+    /// Templates used to render `reso.error_code` and `reso.warn_code` messages
+    pub messages: Catalog,
+    /// What the module has said about the listing so far
+    pub outcome: Outcome,
+    /// When `validate` was called, so that host calls can be timestamped
+    pub started: Instant,
+    /// Every host call the module has made, if we're recording them
+    pub trace: Option<Vec<HostCall>>,
+    /// Statistics about the run, if we're collecting them
+    pub stats: Option<Stats>,
+    /// Limits on how many host calls the module may make and how much text it may hand over
+    pub quotas: Quotas,
+    /// How much of its quotas the module has used
+    usage: Usage,
+    /// The data, with related resources alongside it, for checking field paths against
+    lookup: OnceCell<serde_json::Value>,
+}
+
+impl Context {
+    pub fn new(data: String, previous_data: String, verbose: u8) -> Self {
+        Self {
+            data,
+            previous_data,
+            related: BTreeMap::new(),
+            edited: None,
+            verbose,
+            messages: Catalog::default(),
+            outcome: Outcome::default(),
+            started: Instant::now(),
+            trace: None,
+            stats: None,
+            quotas: Quotas::default(),
+            usage: Usage::default(),
+            lookup: OnceCell::new(),
+        }
+    }
+
+    /// Hand the module related resources through `reso.related`
+    pub fn with_related(mut self, related: BTreeMap<String, String>) -> Self {
+        self.related.extend(related);
+        self
+    }
+
+    /// Report these fields from `reso.changed_fields`, rather than working them out
+    pub fn with_edited(mut self, edited: Vec<String>) -> Self {
+        self.edited = Some(edited);
+        self
+    }
+
+    /// Render coded messages using the given catalog
+    pub fn with_messages(mut self, messages: Catalog) -> Self {
+        self.messages = messages;
+        self
+    }
+
+    /// Limit host calls and messages to these quotas, rather than the defaults
+    pub fn with_quotas(mut self, quotas: Quotas) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
+    /// Record every host call the module makes
+    pub fn recording(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    /// Collect statistics about the run
+    pub fn measuring(mut self) -> Self {
+        self.stats = Some(Stats::default());
+        self
+    }
+
+    /// Check a field path the module handed us, and add a host warning if it doesn't parse or
+    /// doesn't match the data
+    fn check_field(&mut self, field: &str) {
+        let lookup = self.lookup.get_or_init(|| {
+            let mut lookup = serde_json::from_str(&self.data).unwrap_or_default();
+            if let serde_json::Value::Object(object) = &mut lookup {
+                for (name, related) in &self.related {
+                    let related = serde_json::from_str(related).unwrap_or_default();
+                    object.insert(name.clone(), related);
+                }
+            }
+            lookup
+        });
+
+        let checked = field
+            .parse::<FieldPath>()
+            .and_then(|path| path.check(lookup));
+        if let Err(problem) = checked {
+            self.outcome
+                .host_warnings
+                .push(format!("{field}: {problem}"));
+        }
+    }
+
+    /// Hold a message from the module to the message quotas, adding a host warning if it had to
+    /// be truncated
+    fn limit_message<'a>(
+        &mut self,
+        function: &str,
+        message: &'a str,
+    ) -> anyhow::Result<Cow<'a, str>> {
+        let (message, truncated) = self.usage.message(&self.quotas, message)?;
+        if truncated {
+            self.outcome.host_warnings.push(format!(
+                "reso.{function}: message truncated to {} bytes",
+                self.quotas.message_len
+            ));
+        }
+        Ok(message)
+    }
+
+    /// Hold text the module handed us, other than a message, to the text quota
+    fn count(&mut self, text: &str) -> anyhow::Result<()> {
+        self.usage.text(&self.quotas, text)
+    }
+
+    /// Run the body of a host call, keeping track of what went in and out of it. Any host warnings
+    /// the call adds count toward the text quota, as they can repeat what the module handed us.
+    fn call<T: CallResult>(
+        &mut self,
+        function: &'static str,
+        args: &[i32],
+        body: impl FnOnce(&mut Self, &mut HostCall) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let started = Instant::now();
+        let mut call = HostCall::new(function, args);
+        let warnings = self.outcome.host_warnings.len();
+        let result = match self.usage.call(&self.quotas, function) {
+            Ok(()) => body(self, &mut call),
+            Err(err) => Err(err),
+        };
+        let result = result.and_then(|value| {
+            for warning in &self.outcome.host_warnings[warnings..] {
+                self.usage.text(&self.quotas, warning)?;
+            }
+            Ok(value)
+        });
+        let duration = started.elapsed();
+
+        if let Some(stats) = &mut self.stats {
+            stats.record(&call, duration);
+        }
+        if let Some(trace) = &mut self.trace {
+            call.started_us = started.duration_since(self.started).as_micros() as u64;
+            call.duration_us = duration.as_micros() as u64;
+            match &result {
+                Ok(value) => call.result = value.traced(),
+                Err(err) => call.error = Some(err.to_string()),
+            }
+            trace.push(call);
+        }
+
+        result
+    }
+}
+
+/// The value a host function hands back to the module, as far as a trace is concerned
+trait CallResult {
+    fn traced(&self) -> Option<i32>;
+}
+
+impl CallResult for () {
+    fn traced(&self) -> Option<i32> {
+        None
+    }
+}
+
+impl CallResult for i32 {
+    fn traced(&self) -> Option<i32> {
+        Some(*self)
+    }
+}
+
+/// The JSON the module hands to `reso.report`
+#[derive(serde::Deserialize)]
+struct Report {
+    field: String,
+    severity: Severity,
+    message: String,
+    #[serde(default)]
+    rule_id: Option<String>,
+    #[serde(default)]
+    related_fields: Vec<String>,
+}
+
+/// Parse the parameters for a coded message. An empty string or JSON null means no parameters.
+fn parse_params(params: &str) -> anyhow::Result<serde_json::Map<String, serde_json::Value>> {
+    if params.is_empty() {
+        return Ok(serde_json::Map::new());
+    }
+    match serde_json::from_str(params) {
+        Ok(serde_json::Value::Object(params)) => Ok(params),
+        Ok(serde_json::Value::Null) => Ok(serde_json::Map::new()),
+        _ => anyhow::bail!("params was not a JSON object"),
+    }
+}
+
+/// Invoke `$define!(name(args...) -> result)` for every host function, so that each backend can
+/// link all of them with its own runtime's types
+macro_rules! host_functions {
+    ($define:ident) => {
+        $define!(data(len, ptr) -> i32);
+        $define!(previous_data(len, ptr) -> i32);
+        $define!(related(name_len, name_ptr, len, ptr) -> i32);
+        $define!(changed_fields(len, ptr) -> i32);
+        $define!(error(field_len, field_ptr, message_len, message_ptr) -> ());
+        $define!(warn(field_len, field_ptr, message_len, message_ptr) -> ());
+        $define!(error_code(field_len, field_ptr, key_len, key_ptr, params_len, params_ptr) -> ());
+        $define!(warn_code(field_len, field_ptr, key_len, key_ptr, params_len, params_ptr) -> ());
+        $define!(report(len, ptr) -> ());
+        $define!(diagnostic(len, ptr) -> ());
+        $define!(set_required(len, ptr, value) -> ());
+        $define!(set_display(len, ptr, value) -> ());
+        $define!(set_readonly(len, ptr, value) -> ());
+        $define!(set_picklist(field_len, field_ptr, values_len, values_ptr) -> ());
+        $define!(set(field_len, field_ptr, value_len, value_ptr) -> ());
+    };
+}
+pub(crate) use host_functions;
+
+/// The name of every host function, with how many `i32` parameters it takes and whether it
+/// returns an `i32`
+pub fn signatures() -> Vec<(&'static str, usize, bool)> {
+    let mut signatures = Vec::new();
+    macro_rules! signature {
+        ($name:ident($($arg:ident),*) -> $result:ty) => {
+            signatures.push((
+                stringify!($name),
+                [$(stringify!($arg)),*].len(),
+                stringify!($result) == "i32",
+            ));
+        };
+    }
+    host_functions!(signature);
+    signatures
+}
+
+/// reso.data – fill the provided buffer with UTF-8-encoded JSON data. If there is more data
+/// than the module has room for, do nothing and just return the size of the JSON data.
+pub fn data(memory: &mut [u8], context: &mut Context, len: i32, ptr: i32) -> anyhow::Result<i32> {
+    context.call("data", &[len, ptr], |context, call| {
+        let buffer = GuestSlice::new(len, ptr, "data")?;
+
+        let (data_len, written) = buffer.write_if_fits(memory, context.data.as_bytes())?;
+        if written {
+            call.written = Some(context.data.clone());
+        }
+
+        log_call!(context, "(reso.data len:{len} ptr:{ptr}) → {data_len}");
+        Ok(data_len)
+    })
+}
+
+/// reso.previous_data – same as reso.data but with the previous data instead.
+pub fn previous_data(
+    memory: &mut [u8],
+    context: &mut Context,
+    len: i32,
+    ptr: i32,
+) -> anyhow::Result<i32> {
+    context.call("previous_data", &[len, ptr], |context, call| {
+        let buffer = GuestSlice::new(len, ptr, "previous_data")?;
+
+        let (previous_data_len, written) =
+            buffer.write_if_fits(memory, context.previous_data.as_bytes())?;
+        if written {
+            call.written = Some(context.previous_data.clone());
+        }
+
+        log_call!(
+            context,
+            "(reso.previous_data len:{len} ptr:{ptr}) → {previous_data_len}"
+        );
+        Ok(previous_data_len)
+    })
+}
+
+/// reso.changed_fields – same as reso.data, but with a JSON array of the names of the fields that
+/// changed. Those are the fields the caller said were edited, if it did; otherwise every field
+/// whose value differs between the data and the previous data (every field in the data, if there
+/// is no previous data).
+pub fn changed_fields(
+    memory: &mut [u8],
+    context: &mut Context,
+    len: i32,
+    ptr: i32,
+) -> anyhow::Result<i32> {
+    context.call("changed_fields", &[len, ptr], |context, call| {
+        let buffer = GuestSlice::new(len, ptr, "changed_fields")?;
+
+        let changed = match &context.edited {
+            Some(edited) => edited.clone(),
+            None => differing_fields(&context.data, &context.previous_data),
+        };
+        let changed = serde_json::to_string(&changed).unwrap();
+        let (changed_len, written) = buffer.write_if_fits(memory, changed.as_bytes())?;
+        if written {
+            call.written = Some(changed);
+        }
+
+        log_call!(
+            context,
+            "(reso.changed_fields len:{len} ptr:{ptr}) → {changed_len}"
+        );
+        Ok(changed_len)
+    })
+}
+
+/// The top-level fields whose values differ between two versions of a listing, in name order
+///
+/// A missing field counts as null, and null previous data counts as an empty listing.
+fn differing_fields(data: &str, previous_data: &str) -> Vec<String> {
+    let parse = |json: &str| match serde_json::from_str(json) {
+        Ok(serde_json::Value::Object(object)) => object,
+        _ => serde_json::Map::new(),
+    };
+    let (data, previous_data) = (parse(data), parse(previous_data));
+
+    let null = serde_json::Value::Null;
+    let mut fields: Vec<_> = data.keys().chain(previous_data.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    fields
+        .into_iter()
+        .filter(|field| {
+            data.get(*field).unwrap_or(&null) != previous_data.get(*field).unwrap_or(&null)
+        })
+        .cloned()
+        .collect()
+}
+
+/// reso.related – same as reso.data, but with a related resource instead. The resource name
+/// (such as `Media`, `Rooms`, `UnitTypes` or `OpenHouse`) is provided as a len+addr pair. If the
+/// host doesn't have the resource, the module is given JSON null.
+pub fn related(
+    memory: &mut [u8],
+    context: &mut Context,
+    name_len: i32,
+    name_ptr: i32,
+    len: i32,
+    ptr: i32,
+) -> anyhow::Result<i32> {
+    context.call("related", &[name_len, name_ptr, len, ptr], |context, call| {
+        let name = GuestSlice::new(name_len, name_ptr, "name")?.string(memory)?.to_string();
+        call.read = vec![name.clone()];
+        let buffer = GuestSlice::new(len, ptr, "related")?;
+
+        let related = context.related.get(&name).map_or("null", String::as_str);
+        let (related_len, written) = buffer.write_if_fits(memory, related.as_bytes())?;
+        if written {
+            call.written = Some(related.to_string());
+        }
+
+        log_call!(
+            context,
+            "(reso.related name_len:{name_len} name_ptr:{name_ptr} len:{len} ptr:{ptr}) → {related_len}"
+        );
+        Ok(related_len)
+    })
+}
+
+/// reso.error – the field (as specified as a UTF-8 string of length `field_len` that starts in
+/// memory at `field_ptr`) is invalid. The reason is provided in the message (as specified as a
+/// UTF-8 string of length `message_len` that starts in memory at `message_ptr`). Like every field
+/// the module hands to the host, the field may be a path to a nested value, such as
+/// `Media[3].ImageWidth`.
+pub fn error(
+    memory: &mut [u8],
+    context: &mut Context,
+    field_len: i32,
+    field_ptr: i32,
+    message_len: i32,
+    message_ptr: i32,
+) -> anyhow::Result<()> {
+    context.call("error", &[field_len, field_ptr, message_len, message_ptr], |context, call| {
+        let field = GuestSlice::new(field_len, field_ptr, "field")?.string(memory)?;
+        let message = GuestSlice::new(message_len, message_ptr, "message")?.string(memory)?;
+        call.read = vec![field.to_string(), message.to_string()];
+        context.count(field)?;
+
+        log_call!(
+            context,
+            "(reso.error field_len:{field_len} field_ptr:{field_ptr} message_len:{message_len} message_ptr:{message_ptr})"
+        );
+        let message = context.limit_message("error", message)?;
+        context.check_field(field);
+        context.outcome.error(field, message.as_ref());
+
+        Ok(())
+    })
+}
+
+/// reso.warn – the field has a warning. The reason is provided in the message.
+pub fn warn(
+    memory: &mut [u8],
+    context: &mut Context,
+    field_len: i32,
+    field_ptr: i32,
+    message_len: i32,
+    message_ptr: i32,
+) -> anyhow::Result<()> {
+    context.call("warn", &[field_len, field_ptr, message_len, message_ptr], |context, call| {
+        let field = GuestSlice::new(field_len, field_ptr, "field")?.string(memory)?;
+        let message = GuestSlice::new(message_len, message_ptr, "message")?.string(memory)?;
+        call.read = vec![field.to_string(), message.to_string()];
+        context.count(field)?;
+
+        log_call!(
+            context,
+            "(reso.warn field_len:{field_len} field_ptr:{field_ptr} message_len:{message_len} message_ptr:{message_ptr})"
+        );
+        let message = context.limit_message("warn", message)?;
+        context.check_field(field);
+        context.outcome.warn(field, message.as_ref());
+
+        Ok(())
+    })
+}
+
+/// reso.error_code – like reso.error, but the module provides a message key (len+addr) and
+/// parameters (len+addr of a JSON object) instead of a message. The host renders the message in
+/// the user's locale.
+#[allow(clippy::too_many_arguments)]
+pub fn error_code(
+    memory: &mut [u8],
+    context: &mut Context,
+    field_len: i32,
+    field_ptr: i32,
+    key_len: i32,
+    key_ptr: i32,
+    params_len: i32,
+    params_ptr: i32,
+) -> anyhow::Result<()> {
+    let args = [
+        field_len, field_ptr, key_len, key_ptr, params_len, params_ptr,
+    ];
+    context.call("error_code", &args, |context, call| {
+        let field = GuestSlice::new(field_len, field_ptr, "field")?.string(memory)?;
+        let key = GuestSlice::new(key_len, key_ptr, "key")?.string(memory)?;
+        let params = GuestSlice::new(params_len, params_ptr, "params")?.string(memory)?;
+        call.read = vec![field.to_string(), key.to_string(), params.to_string()];
+        for text in [field, key, params] {
+            context.count(text)?;
+        }
+        let params = parse_params(params)?;
+
+        log_call!(
+            context,
+            "(reso.error_code field_len:{field_len} field_ptr:{field_ptr} key_len:{key_len} key_ptr:{key_ptr} params_len:{params_len} params_ptr:{params_ptr})"
+        );
+        let message = context.messages.render(key, &params);
+        let message = context.limit_message("error_code", &message)?;
+        context.check_field(field);
+        context.outcome.error(field, message.as_ref());
+
+        Ok(())
+    })
+}
+
+/// reso.warn_code – like reso.warn, but with a message key and parameters, as reso.error_code.
+#[allow(clippy::too_many_arguments)]
+pub fn warn_code(
+    memory: &mut [u8],
+    context: &mut Context,
+    field_len: i32,
+    field_ptr: i32,
+    key_len: i32,
+    key_ptr: i32,
+    params_len: i32,
+    params_ptr: i32,
+) -> anyhow::Result<()> {
+    let args = [
+        field_len, field_ptr, key_len, key_ptr, params_len, params_ptr,
+    ];
+    context.call("warn_code", &args, |context, call| {
+        let field = GuestSlice::new(field_len, field_ptr, "field")?.string(memory)?;
+        let key = GuestSlice::new(key_len, key_ptr, "key")?.string(memory)?;
+        let params = GuestSlice::new(params_len, params_ptr, "params")?.string(memory)?;
+        call.read = vec![field.to_string(), key.to_string(), params.to_string()];
+        for text in [field, key, params] {
+            context.count(text)?;
+        }
+        let params = parse_params(params)?;
+
+        log_call!(
+            context,
+            "(reso.warn_code field_len:{field_len} field_ptr:{field_ptr} key_len:{key_len} key_ptr:{key_ptr} params_len:{params_len} params_ptr:{params_ptr})"
+        );
+        let message = context.messages.render(key, &params);
+        let message = context.limit_message("warn_code", &message)?;
+        context.check_field(field);
+        context.outcome.warn(field, message.as_ref());
+
+        Ok(())
+    })
+}
+
+/// reso.report – report an error or warning with more detail than reso.error and reso.warn.
+/// Takes a single len+addr pair that is expected to be a JSON object with `field`, `severity`
+/// ("error" or "warning") and `message`, and optionally the `rule_id` of the MLS rule that
+/// produced it and `related_fields` that are also involved.
+pub fn report(memory: &mut [u8], context: &mut Context, len: i32, ptr: i32) -> anyhow::Result<()> {
+    context.call("report", &[len, ptr], |context, call| {
+        let report = GuestSlice::new(len, ptr, "report")?.string(memory)?;
+        call.read = vec![report.to_string()];
+        let report = match serde_json::from_str::<Report>(report) {
+            Ok(report) => report,
+            Err(err) => anyhow::bail!("report was not valid: {err}"),
+        };
+
+        log_call!(context, "(reso.report len:{len} ptr:{ptr})");
+        context.count(&report.field)?;
+        for text in report.rule_id.iter().chain(&report.related_fields) {
+            context.count(text)?;
+        }
+        let message = context
+            .limit_message("report", &report.message)?
+            .into_owned();
+        context.check_field(&report.field);
+        context.outcome.report(
+            &report.field,
+            report.severity,
+            Message {
+                message,
+                rule_id: report.rule_id,
+                related_fields: report.related_fields,
+                module: None,
+            },
+        );
+
+        Ok(())
+    })
+}
+
+/// reso.diagnostic – a way for modules to output information. Takes a single string (represented
+/// by a len+address pair).
+pub fn diagnostic(
+    memory: &mut [u8],
+    context: &mut Context,
+    len: i32,
+    ptr: i32,
+) -> anyhow::Result<()> {
+    context.call("diagnostic", &[len, ptr], |context, call| {
+        log_call!(context, "(reso.diagnostic len:{len} ptr:{ptr})");
+        let diagnostic = GuestSlice::new(len, ptr, "diagnostic")?.string_lax(memory)?;
+        call.read = vec![diagnostic.to_string()];
+        let diagnostic = context.limit_message("diagnostic", &diagnostic)?;
+
+        // log_call!(context, "(reso.diagnostic len:{len} ptr:{ptr})");
+        if context.verbose > 0 {
+            println!("ℹ️  {diagnostic}");
+        }
+
+        Ok(())
+    })
+}
+
+/// reso.set_required – set whether the field (len+address) is required (0 is not required, any
+/// other value is required)
+pub fn set_required(
+    memory: &mut [u8],
+    context: &mut Context,
+    len: i32,
+    ptr: i32,
+    value: i32,
+) -> anyhow::Result<()> {
+    context.call("set_required", &[len, ptr, value], |context, call| {
+        let field = GuestSlice::new(len, ptr, "field")?.string(memory)?;
+        call.read = vec![field.to_string()];
+        context.count(field)?;
+
+        log_call!(
+            context,
+            "(reso.set_required len:{len} ptr:{ptr} value:{value})"
+        );
+        context.outcome.set_required(field, value != 0);
+
+        Ok(())
+    })
+}
+
+/// reso.set_display – set whether the field (len+address) should be displayed (0 is do not
+/// display, any other value is yes display the field)
+pub fn set_display(
+    memory: &mut [u8],
+    context: &mut Context,
+    len: i32,
+    ptr: i32,
+    value: i32,
+) -> anyhow::Result<()> {
+    context.call("set_display", &[len, ptr, value], |context, call| {
+        let field = GuestSlice::new(len, ptr, "field")?.string(memory)?;
+        call.read = vec![field.to_string()];
+        context.count(field)?;
+
+        log_call!(
+            context,
+            "(reso.set_visible len:{len} ptr:{ptr} value:{value})"
+        );
+        context.outcome.set_visible(field, value != 0);
+
+        Ok(())
+    })
+}
+
+/// reso.set_readonly – set whether the field (len+address) is read-only (0 is editable, any
+/// other value is read-only)
+pub fn set_readonly(
+    memory: &mut [u8],
+    context: &mut Context,
+    len: i32,
+    ptr: i32,
+    value: i32,
+) -> anyhow::Result<()> {
+    context.call("set_readonly", &[len, ptr, value], |context, call| {
+        let field = GuestSlice::new(len, ptr, "field")?.string(memory)?;
+        call.read = vec![field.to_string()];
+        context.count(field)?;
+
+        log_call!(
+            context,
+            "(reso.set_readonly len:{len} ptr:{ptr} value:{value})"
+        );
+        context.outcome.set_readonly(field, value != 0);
+
+        Ok(())
+    })
+}
+
+/// reso.set_picklist – limit the field to a set of values. The field is provided as a len+addr
+/// pair. The values are provided as a len+addr pair that is expected to be a JSON array.
+pub fn set_picklist(
+    memory: &mut [u8],
+    context: &mut Context,
+    field_len: i32,
+    field_ptr: i32,
+    values_len: i32,
+    values_ptr: i32,
+) -> anyhow::Result<()> {
+    context.call("set_picklist", &[field_len, field_ptr, values_len, values_ptr], |context, call| {
+        let field = GuestSlice::new(field_len, field_ptr, "field")?.string(memory)?;
+        let values = GuestSlice::new(values_len, values_ptr, "values")?.string(memory)?;
+        call.read = vec![field.to_string(), values.to_string()];
+        context.count(field)?;
+        context.count(values)?;
+        let Ok(values) = serde_json::from_str::<Vec<serde_json::Value>>(values) else {
+            anyhow::bail!("values was not a JSON array");
+        };
+
+        log_call!(
+            context,
+            "(reso.set_picklist field_len:{field_len} field_ptr:{field_ptr} values_len:{values_len} values_ptr:{values_ptr})"
+        );
+        context.outcome.set_picklist(field, values);
+
+        Ok(())
+    })
+}
+
+/// reso.set – set a field to the provided value. The field is provided as a len+addr pair. The
+/// value is provided as a len+addr pair that is expected to be JSON data.
+pub fn set(
+    memory: &mut [u8],
+    context: &mut Context,
+    field_len: i32,
+    field_ptr: i32,
+    value_len: i32,
+    value_ptr: i32,
+) -> anyhow::Result<()> {
+    context.call("set", &[field_len, field_ptr, value_len, value_ptr], |context, call| {
+        let field = GuestSlice::new(field_len, field_ptr, "field")?.string(memory)?;
+        let value = GuestSlice::new(value_len, value_ptr, "value")?.string(memory)?;
+        call.read = vec![field.to_string(), value.to_string()];
+        context.count(field)?;
+        context.count(value)?;
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(value) else {
+            anyhow::bail!("value was not a valid JSON value");
+        };
+
+        log_call!(
+            context,
+            "(reso.set field_len:{field_len} field_ptr:{field_ptr} value_len:{value_len} value_ptr:{value_ptr})"
+        );
+        context.check_field(field);
+        context.outcome.set_value(field, value);
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::differing_fields;
+    use serde_json::json;
+
+    fn differing(data: serde_json::Value, previous_data: serde_json::Value) -> Vec<String> {
+        differing_fields(&data.to_string(), &previous_data.to_string())
+    }
+
+    #[test]
+    fn fields_that_changed_came_or_went_differ() {
+        assert_eq!(
+            differing(
+                json!({ "ListPrice": 2, "City": "Springfield", "PostalCode": "12345" }),
+                json!({ "ListPrice": 1, "City": "Springfield", "Country": "US" }),
+            ),
+            ["Country", "ListPrice", "PostalCode"]
+        );
+    }
+
+    #[test]
+    fn nested_changes_are_reported_on_the_top_level_field() {
+        assert_eq!(
+            differing(
+                json!({ "Media": [{ "Order": 1 }], "MediaCount": 1 }),
+                json!({ "Media": [{ "Order": 2 }], "MediaCount": 1 }),
+            ),
+            ["Media"]
+        );
+    }
+
+    #[test]
+    fn missing_fields_count_as_null() {
+        assert_eq!(
+            differing(json!({ "ListPrice": null }), json!({})),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            differing(json!({ "ListPrice": 1 }), json!(null)),
+            ["ListPrice"]
+        );
+        assert_eq!(
+            differing(json!({}), json!({ "ListPrice": 1 })),
+            ["ListPrice"]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! A reference host for business rules compiled to WebAssembly
+//!
+//! A validation module exports a `validate` function and talks to the host through the `reso.*`
+//! host functions defined in [`host`]. [`Validator`] runs a module against a [`Context`] and hands
+//! back everything the module said about the listing, using one of the runtimes in [`backend`].
+
+use std::{collections::BTreeMap, path::Path};
+
+pub mod backend;
+pub mod coverage;
+mod error;
+pub mod history;
+pub mod host;
+pub mod memory;
+pub mod messages;
+pub mod metadata;
+pub mod odata;
+pub mod outcome;
+pub mod path;
+pub mod quota;
+pub mod report;
+pub mod schema;
+pub mod sections;
+pub mod signature;
+pub mod stats;
+pub mod trace;
+
+#[cfg(feature = "async")]
+pub use backend::AsyncValidator;
+use backend::Backend;
+pub use backend::Runtime;
+pub use error::Error;
+pub use host::Context;
+
+/// A module whose `validate` function ran, either to completion or until it trapped
+pub struct Finished {
+    /// The context, holding everything the module said before it finished
+    pub context: Context,
+    /// Why `validate` trapped, if it did
+    pub trap: Option<anyhow::Error>,
+    /// The coverage counters the module exports, keyed by name without the `reso.coverage.`
+    /// prefix
+    pub coverage: BTreeMap<String, u64>,
+}
+
+/// A compiled validation module, ready to be run against any number of listings
+pub struct Validator {
+    backend: Box<dyn Backend>,
+}
+
+impl Validator {
+    /// Compile the module in the given file with wasmtime
+    ///
+    /// `measure` turns on fuel consumption, so that contexts that are collecting statistics can
+    /// report how many instructions were executed.
+    pub fn from_file(webassembly: &Path, measure: bool) -> Result<Self, Error> {
+        Self::with_runtime(Runtime::default(), webassembly, measure)
+    }
+
+    /// Compile the module in the given file with the given runtime
+    pub fn with_runtime(
+        runtime: Runtime,
+        webassembly: &Path,
+        measure: bool,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            backend: runtime.compile(webassembly, measure)?,
+        })
+    }
+
+    /// Compile a module that's already in memory with the given runtime
+    pub fn from_bytes(runtime: Runtime, webassembly: &[u8], measure: bool) -> Result<Self, Error> {
+        Ok(Self {
+            backend: runtime.compile_bytes(webassembly, measure)?,
+        })
+    }
+
+    /// Compile the contents of the module in the given file with the given runtime
+    ///
+    /// For modules that had to be read before they were compiled, such as to check their
+    /// signature: compiling the contents that were checked, rather than reading the file again,
+    /// means the file can't be swapped out in between.
+    pub fn from_contents(
+        runtime: Runtime,
+        webassembly: &Path,
+        contents: &[u8],
+        measure: bool,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            backend: runtime.compile_contents(webassembly, contents, measure)?,
+        })
+    }
+
+    /// The runtime the module was compiled with
+    pub fn runtime(&self) -> Runtime {
+        self.backend.runtime()
+    }
+
+    /// Instantiate the module in a fresh store and call its `validate` function
+    pub fn run(&self, context: Context) -> Result<Finished, Error> {
+        self.backend.run(context)
+    }
+}
+
+/// Load the module, instantiate it, and call its `validate` function
+pub fn execute(webassembly: &Path, context: Context) -> Result<Finished, Error> {
+    Validator::from_file(webassembly, context.stats.is_some())?.run(context)
+}
+
+/// Read and parse a JSON file
+pub fn read_json(path: &Path) -> Result<serde_json::Value, Error> {
+    let contents = std::fs::read(path).map_err(|source| Error::ReadInput {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_slice(&contents).map_err(|source| Error::ParseInput {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// A listing's `ListingKey`, which identifies it across versions
+pub fn listing_key(data: &serde_json::Value) -> Option<String> {
+    match data.get("ListingKey")? {
+        serde_json::Value::String(key) => Some(key.clone()),
+        key @ serde_json::Value::Number(_) => Some(key.to_string()),
+        _ => None,
+    }
+}
+
+/// Find the child collections that were expanded inline in a listing, such as the `Media` array of
+/// an OData `$expand`, so they can be handed to the module through `reso.related`
+///
+/// Returns stringified JSON keyed by collection name.
+pub fn expanded_collections(data: &serde_json::Value) -> BTreeMap<String, String> {
+    let Some(object) = data.as_object() else {
+        return BTreeMap::new();
+    };
+
+    object
+        .iter()
+        .filter(|(_, value)| value.is_array())
+        .map(|(name, value)| (name.clone(), serde_json::to_string(value).unwrap()))
+        .collect()
+}
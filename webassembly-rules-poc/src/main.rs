@@ -1,24 +1,311 @@
-use clap::Parser;
-use std::{borrow::Cow, path::PathBuf};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use webassembly_rules_poc::{
+    coverage::{self, Coverage},
+    execute, expanded_collections,
+    history::History,
+    listing_key,
+    messages::Catalog,
+    metadata::Metadata,
+    odata,
+    outcome::Change,
+    quota::Quotas,
+    read_json,
+    report::{self, ListingReport},
+    schema::Schema,
+    signature::{self, TrustedKeys},
+    trace::Trace,
+    Context, Error, Finished, Runtime, Validator,
+};
+
+mod repl;
+mod stdio;
+mod watch;
+
+/// Validation ran and reported no errors
+const EXIT_SUCCESS: u8 = 0;
+/// An input couldn't be read, an output couldn't be written, or the engine failed
+const EXIT_FAILURE: u8 = 1;
+/// The module couldn't be compiled
+const EXIT_COMPILE: u8 = 2;
+/// The module couldn't be instantiated
+const EXIT_INSTANTIATE: u8 = 3;
+/// The module has no `validate` function
+const EXIT_MISSING_VALIDATE: u8 = 4;
+/// `validate` trapped
+const EXIT_TRAP: u8 = 5;
+/// Replaying a trace produced different host calls than were recorded
+const EXIT_REPLAY_DIVERGED: u8 = 6;
+/// Validation ran and reported at least one error
+const EXIT_VALIDATION_ERRORS: u8 = 7;
+/// Validation ran and reported a warning, and `--fail-on-warnings` was given
+const EXIT_VALIDATION_WARNINGS: u8 = 8;
+/// `--differential` was given and the runtimes disagreed about a listing
+const EXIT_RUNTIMES_DISAGREED: u8 = 9;
+/// `--trusted-keys` was given and the module isn't signed by one of them
+const EXIT_UNVERIFIED: u8 = 10;
+/// `--check-schema` was given and the data didn't have the RESO Data Dictionary's types
+const EXIT_SCHEMA: u8 = 11;
+/// The options can't be used with the inputs given
+const EXIT_USAGE: u8 = 12;
+
+/// Documentation for the exit codes, shown at the end of `--help`
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Validation ran and reported no errors
+  1  An input couldn't be read, an output couldn't be written, or the engine failed
+  2  The module couldn't be compiled
+  3  The module couldn't be instantiated
+  4  The module has no `validate` function
+  5  `validate` trapped
+  6  Replay diverged from the recorded trace
+  7  Validation reported at least one error
+  8  Validation reported a warning and --fail-on-warnings was given
+  9  The runtimes disagreed about a listing in a --differential run
+ 10  The module isn't signed by one of the --trusted-keys
+ 11  The data didn't have the RESO Data Dictionary's types in a --check-schema run
+ 12  The options can't be used with the inputs given, such as --record with several listings";
 
 /// The struct that represents command line arguments
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = EXIT_CODES_HELP)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The validator in WebAssembly format
-    #[arg(short, long, value_name = "FILE")]
-    webassembly: PathBuf,
+    #[arg(short, long, value_name = "FILE", required_unless_present = "stdio")]
+    webassembly: Option<PathBuf>,
+
+    /// A module to run after --webassembly, layering what it says on top
+    ///
+    /// Give more than once to stack several, such as board-specific rules on top of statewide
+    /// ones. Every module runs in its own store against the same listing. Errors and warnings from
+    /// all of them are kept, and settings such as `required` from later modules take precedence
+    /// over earlier ones. Everything is tagged with the module it came from. If a module traps,
+    /// the ones after it aren't run.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["record", "stats"])]
+    overlay: Vec<PathBuf>,
 
     /// The path to the JSON data
-    #[arg(short, long, value_name = "FILE")]
-    data: PathBuf,
+    ///
+    /// Give more than one file, or a directory of `.json` files, to validate a whole corpus of
+    /// listings with the same module. A file can also hold an OData collection response
+    /// (`{"value": [...]}`), in which case every listing in it is validated.
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        required_unless_present_any = ["odata_url", "stdio"],
+        num_args = 1..
+    )]
+    data: Vec<PathBuf>,
+
+    /// Fetch the listings to validate from a RESO Web API (OData) collection
+    ///
+    /// Every page is fetched, following `@odata.nextLink`, and every listing on them validated.
+    #[arg(long, value_name = "URL")]
+    odata_url: Option<String>,
+
+    /// The bearer token to send with --odata-url requests
+    #[arg(
+        long,
+        env = "ODATA_TOKEN",
+        hide_env_values = true,
+        requires = "odata_url"
+    )]
+    odata_token: Option<String>,
 
     /// The path to the JSON previous data
     ///
-    /// If this is not supplied, null will be provided as the previous data.
+    /// If this is not supplied, null will be provided as the previous data. When validating more
+    /// than one listing, every listing gets the same previous data.
     #[arg(short, long, value_name = "FILE")]
     previous_data: Option<PathBuf>,
 
+    /// A directory of previously accepted listings to take previous data from
+    ///
+    /// Each listing's previous data is the version of it with the same `ListingKey` in the
+    /// directory, or null if there isn't one. After a listing is validated without errors, it
+    /// replaces that version, so the next run sees it as the previous data.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["previous_data", "watch"])]
+    history: Option<PathBuf>,
+
+    /// Fields the user edited, reported to the module by `reso.changed_fields`
+    ///
+    /// Without this, the changed fields are those whose values differ between the data and the
+    /// previous data. Give more than once, or separate with commas.
+    #[arg(long, value_name = "FIELD", value_delimiter = ',')]
+    edited: Option<Vec<String>>,
+
+    /// Only report on these fields, and on errors and warnings that name them as related
+    ///
+    /// Nested fields are included, so `Rooms` covers `Rooms[0].RoomLevel`. Give more than once,
+    /// or separate with commas.
+    #[arg(long, value_name = "FIELD", value_delimiter = ',')]
+    fields: Option<Vec<String>>,
+
+    /// A related resource to hand to the module through `reso.related`, as NAME=FILE
+    ///
+    /// For example `--related Media=media.json`. The file should hold the expanded collection,
+    /// usually a JSON array of records. Collections expanded inline in the listing (such as an
+    /// OData `Media` array) are handed over automatically; this option takes precedence over them.
+    #[arg(long, value_name = "NAME=FILE", value_parser = parse_related)]
+    related: Vec<(String, PathBuf)>,
+
+    /// A directory of message catalogs used to render coded messages
+    ///
+    /// Holds one JSON file per locale (`en.json`, `es.json`, `es-MX.json`, ...) mapping message
+    /// keys to templates such as `"List price must be at least {min}"`. Messages without a
+    /// template are shown as their key.
+    #[arg(long, value_name = "DIR")]
+    messages: Option<PathBuf>,
+
+    /// The locale to render coded messages in
+    ///
+    /// A regional locale like `es-MX` falls back to its language (`es`).
+    #[arg(long, default_value = "en")]
+    locale: String,
+
+    /// Write the listing, with every value the module set applied, to a file
+    ///
+    /// Fields set with nested paths such as `Rooms[0].RoomLevel` are applied inside the nested
+    /// objects and arrays.
+    #[arg(long, value_name = "FILE")]
+    write_data: Option<PathBuf>,
+
+    /// Record every host call the module makes to a trace file
+    ///
+    /// The trace holds the data the module was given and every host call it made, with its
+    /// arguments, the bytes that went in and out of it, and timing. Use the `replay` command to
+    /// re-run a module against the trace.
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// The WebAssembly runtime to run the module with
+    #[arg(long, value_enum, default_value_t = Runtime::default())]
+    runtime: Runtime,
+
+    /// Run every listing with both wasmtime and wasmi, and report any difference between them
+    #[cfg(feature = "wasmi")]
+    #[arg(long, conflicts_with_all = ["runtime", "overlay", "stdio"])]
+    differential: bool,
+
+    /// Check the data and previous data against the RESO Data Dictionary's types first
+    ///
+    /// A listing with a field of the wrong type, such as a `ListPrice` that's a string, is
+    /// reported as a problem with the input and the module isn't run on it, rather than leaving
+    /// the module to fail on it. The rest of the listings are still validated. Previous data
+    /// taken from --history is checked the same way, and --previous-data and --related files have
+    /// to match before anything is run.
+    #[arg(long)]
+    check_schema: bool,
+
+    /// Extra field types to check, as a JSON object mapping resources to fields to types
+    ///
+    /// Types are `String`, `Boolean`, `Number`, `Integer`, `Date`, `Timestamp` and `StringList`,
+    /// as in `{"Property": {"ListPriceLow": "Number"}}`. Implies --check-schema.
+    #[arg(long, value_name = "FILE")]
+    schema: Option<PathBuf>,
+
+    /// Refuse to run the module unless it's signed by one of the keys in this file
+    ///
+    /// The file is a JSON object mapping each signer to their hex-encoded ed25519 public key. Use
+    /// the `sign` command to sign a module.
+    #[arg(long, value_name = "FILE")]
+    trusted_keys: Option<PathBuf>,
+
+    /// Run modules that fail verification against --trusted-keys anyway, with a warning
+    #[arg(long, requires = "trusted_keys")]
+    allow_unverified: bool,
+
+    /// How to print the outcome
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+
+    /// Report statistics about the run
+    ///
+    /// Includes compile, instantiation and `validate` times, the fuel `validate` consumed, the peak
+    /// size of the module's linear memory, and the number of calls and bytes transferred for each
+    /// host function.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "human"
+    )]
+    stats: Option<StatsFormat>,
+
+    /// Report how much of the module the batch exercised
+    ///
+    /// Counts how many times each rule was evaluated and took its action, for modules built by
+    /// rcp19-to-wasm, and how many times each function ran, for any module. Printed after every
+    /// listing has been validated; to stderr unless the format is `human`.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "human",
+        conflicts_with_all = ["watch", "interactive"]
+    )]
+    coverage: Option<StatsFormat>,
+
+    /// The most times the module may call any one host function before it's stopped
+    #[arg(long, value_name = "CALLS", default_value_t = Quotas::default().calls_per_function)]
+    max_calls: u64,
+
+    /// The most bytes of text the module may hand over before it's stopped: messages, field names,
+    /// values, picklists, rule IDs and related fields, and the host warnings they cause
+    #[arg(long, value_name = "BYTES", default_value_t = Quotas::default().message_bytes)]
+    max_message_bytes: usize,
+
+    /// The longest a single message may be, in bytes; longer ones are truncated with a warning
+    #[arg(long, value_name = "BYTES", default_value_t = Quotas::default().message_len)]
+    max_message_len: usize,
+
+    /// Exit with a failure code if the module reported any warnings, not just errors
+    #[arg(long)]
+    fail_on_warnings: bool,
+
+    /// Keep running, and re-run whenever the module or any of its inputs change
+    ///
+    /// Watches the module, the data, previous data, related resources and message catalogs. After
+    /// every re-run, shows what changed in each listing's outcome since the run before it.
+    #[arg(long, conflicts_with = "format")]
+    watch: bool,
+
+    /// Edit the listing interactively, re-running the module after every change
+    ///
+    /// Reads commands such as `set ListPrice 250000` and `unset ClosePrice` from the terminal,
+    /// and shows which errors, warnings and settings appeared, cleared or changed after each one.
+    /// Type `help` for the full list.
+    #[arg(long, conflicts_with_all = ["watch", "format", "record", "write_data", "history"])]
+    interactive: bool,
+
+    /// Serve line-delimited JSON-RPC 2.0 on stdin and stdout, for embedding in other services
+    ///
+    /// Methods are `load_module` (`{"path": ...}`, returning a module id and its metadata),
+    /// `validate` (`{"module": ..., "data": ...}`, plus optional `previous_data`, `edited`,
+    /// `fields` and `related`, returning the outcome) and `unload` (`{"module": ...}`). Modules
+    /// stay compiled until they're unloaded. Runs until stdin closes. --runtime, --overlay,
+    /// --related, --messages, schema checks, signature checks and quotas apply to every request.
+    /// With --history, a request without `previous_data` takes it from the history, and a listing
+    /// validated without errors is recorded there.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "webassembly", "data", "odata_url", "previous_data", "edited",
+            "fields", "write_data", "record", "format", "stats", "coverage",
+            "fail_on_warnings", "watch", "interactive", "verbose"
+        ]
+    )]
+    stdio: bool,
+
     /// Turn debugging information on
     ///
     /// Use once to get any wasm calls to the `diagnostic` host call. Use twice to output detailed
@@ -27,524 +314,761 @@ struct Args {
     verbose: u8,
 }
 
-/// Quick little helper that helps with logging host calls.
-macro_rules! log_call {
-    ($context:expr, $($rest:tt)*) => {
-        if $context.verbose >= 2 {
-            println!("\x1b[3;90m{}\x1b[0m", format_args!($($rest)*));
-        }
-    };
+/// How to print the outcome of a run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Emoji-decorated lines meant for a terminal
+    Human,
+    /// A single JSON document covering every listing
+    Json,
+    /// JUnit XML, with a test case per listing and a failure per error
+    Junit,
+    /// A SARIF log, with a result per error and warning
+    Sarif,
 }
 
-fn main() {
-    // Parse the command line arguments
-    let args = Args::parse();
-    // Build up a context based on the arguments
-    let context = Context::from_args(&args);
-
-    // Base wasmtime code we need for a new engine.
-    let config = wasmtime::Config::new();
-    let engine = match wasmtime::Engine::new(&config) {
-        Ok(engine) => engine,
-        Err(err) => {
-            eprintln!("Failed to create engine: {err}");
-            std::process::exit(1);
-        }
-    };
+/// How to print statistics and coverage
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum StatsFormat {
+    Human,
+    Json,
+}
 
-    // Parse the module from the passed in webassembly.
-    let module = match wasmtime::Module::from_file(&engine, &args.webassembly) {
-        Ok(module) => module,
-        Err(err) => {
-            eprintln!(
-                "Failed to create module from {}: {err}",
-                args.webassembly.to_string_lossy(),
-            );
-            std::process::exit(2);
-        }
-    };
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Re-run a module against a recorded trace and report any divergence from it
+    ///
+    /// The module is given exactly the data that was recorded. Every host call it makes is
+    /// compared against the recorded calls; timing is ignored.
+    Replay {
+        /// The validator in WebAssembly format
+        #[arg(short, long, value_name = "FILE")]
+        webassembly: PathBuf,
+
+        /// The trace recorded with `--record`
+        #[arg(short, long, value_name = "FILE")]
+        trace: PathBuf,
+
+        /// Turn debugging information on
+        #[arg(short, long, action = clap::ArgAction::Count)]
+        verbose: u8,
+    },
+    /// Show what a module says about itself in its `reso.metadata` section
+    ///
+    /// That's the MLS and version of the rules it implements, when and by what it was built, and
+    /// the resources it validates.
+    Inspect {
+        /// The validator in WebAssembly format
+        #[arg(short, long, value_name = "FILE")]
+        webassembly: PathBuf,
+
+        /// Print the metadata as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Sign a module so it can be verified with `--trusted-keys`
+    ///
+    /// The signature is kept in a `reso.signature` custom section and covers the rest of the
+    /// module. Any signature the module already has is replaced.
+    Sign {
+        /// The validator in WebAssembly binary format
+        #[arg(short, long, value_name = "FILE")]
+        webassembly: PathBuf,
+
+        /// The ed25519 signing key, as 64 hex digits, such as the output of `openssl rand -hex 32`
+        #[arg(short, long, value_name = "FILE")]
+        key: PathBuf,
+
+        /// Who is signing the module, usually an MLS id
+        #[arg(short, long)]
+        signer: String,
+
+        /// Where to write the signed module, instead of replacing the original
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
 
-    // Instantiate a new instance.
-    let mut store = wasmtime::Store::new(&engine, context);
-    let linker = create_linker(&engine, &module);
-    let instance = match linker.instantiate(&mut store, &module) {
-        Ok(instance) => instance,
-        Err(err) => {
-            eprintln!("Failed to instantiate module: {err}");
-            std::process::exit(3);
-        }
-    };
+fn main() -> ExitCode {
+    // Parse the command line arguments
+    let args = Args::parse();
 
-    // Find the validate function in the module.
-    let function = match instance.get_typed_func::<(), ()>(&mut store, "validate") {
-        Ok(function) => function,
-        Err(err) => {
-            eprintln!("Failed to get `validate` function from WebAssembly module: {err}");
-            std::process::exit(4);
-        }
+    let result = match &args.command {
+        Some(Command::Replay {
+            webassembly,
+            trace,
+            verbose,
+        }) => replay(webassembly, trace, *verbose),
+        Some(Command::Inspect { webassembly, json }) => inspect(webassembly, *json),
+        Some(Command::Sign {
+            webassembly,
+            key,
+            signer,
+            output,
+        }) => sign(webassembly, key, signer, output.as_deref()),
+        None if args.watch => watch::watch(&args),
+        None if args.interactive => repl::repl(&args),
+        None if args.stdio => stdio::serve(&args),
+        None => run(&args),
     };
 
-    // And call the validate function!
-    match function.call(&mut store, ()) {
-        Ok(()) => {}
+    match result {
+        Ok(code) => ExitCode::from(code),
         Err(err) => {
-            eprintln!("Execution failed: {err}");
-            std::process::exit(5);
+            eprintln!("{err}");
+            ExitCode::from(exit_code(&err))
         }
     }
-
-    // That's it. We're done.
-    let context = store.into_data();
-    log_call!(context, "Validation program finished");
 }
 
-struct Context {
-    /// Stringified version of the JSON for the data
-    pub data: String,
-    /// Stringified version of the JSON for the previous data
-    pub previous_data: String,
-    /// Which verbosity level we're at
-    pub verbose: u8,
+/// The exit code for a run that failed before producing a complete outcome
+fn exit_code(err: &Error) -> u8 {
+    match err {
+        Error::ReadInput { .. }
+        | Error::ParseInput { .. }
+        | Error::Fetch { .. }
+        | Error::WriteOutput { .. }
+        | Error::Engine(_) => EXIT_FAILURE,
+        Error::Compile { .. } => EXIT_COMPILE,
+        Error::Instantiate(_) => EXIT_INSTANTIATE,
+        Error::MissingValidate(_) => EXIT_MISSING_VALIDATE,
+        Error::Trap(_) => EXIT_TRAP,
+        Error::Unverified { .. } => EXIT_UNVERIFIED,
+        Error::Schema { .. } => EXIT_SCHEMA,
+        Error::Usage(_) => EXIT_USAGE,
+    }
 }
 
-impl Context {
-    pub fn from_args(args: &Args) -> Self {
-        let data_contents = match std::fs::read(&args.data) {
-            Ok(contents) => contents,
-            Err(err) => {
-                eprintln!(
-                    "Failed to read JSON from '{}': {err}",
-                    args.data.to_string_lossy()
-                );
-                std::process::exit(1);
-            }
-        };
-        let data: serde_json::Value = match serde_json::from_slice(&data_contents) {
-            Ok(data) => data,
-            Err(err) => {
-                eprintln!(
-                    "Contents of '{}' was not JSON: {err}",
-                    args.data.to_string_lossy()
-                );
-                std::process::exit(1);
-            }
-        };
-
-        let previous_data = if let Some(previous_data) = &args.previous_data {
-            let previous_data_contents = match std::fs::read(previous_data) {
-                Ok(contents) => contents,
-                Err(err) => {
-                    eprintln!(
-                        "Failed to read JSON from '{}': {err}",
-                        previous_data.to_string_lossy()
-                    );
-                    std::process::exit(1);
-                }
-            };
-            let previous_data: serde_json::Value =
-                match serde_json::from_slice(&previous_data_contents) {
-                    Ok(data) => data,
-                    Err(err) => {
-                        eprintln!(
-                            "Contents of '{}' was not JSON: {err}",
-                            previous_data.to_string_lossy()
-                        );
-                        std::process::exit(1);
-                    }
-                };
-            previous_data
-        } else {
-            serde_json::Value::Null
-        };
-
-        Self {
-            data: serde_json::to_string(&data).unwrap(),
-            previous_data: serde_json::to_string(&previous_data).unwrap(),
-            verbose: args.verbose,
+/// Run the validator against the data given on the command line
+fn run(args: &Args) -> Result<u8, Error> {
+    let reports = validate_listings(args)?;
+
+    let webassembly = args.webassembly.as_deref().expect("required by clap");
+    let module = webassembly.to_string_lossy();
+    match args.format {
+        Format::Human => {}
+        Format::Json => {
+            let metadata = Metadata::from_file(webassembly)?;
+            println!("{}", report::to_json(&module, metadata.as_ref(), &reports))
         }
+        Format::Junit => print!("{}", report::to_junit(&module, &reports)),
+        Format::Sarif => println!("{}", report::to_sarif(&module, &reports)),
+    }
+
+    if reports
+        .iter()
+        .any(|report| !report.schema_mismatches.is_empty())
+    {
+        return Ok(EXIT_SCHEMA);
+    }
+    if reports.iter().any(|report| report.trap.is_some()) {
+        return Ok(EXIT_TRAP);
+    }
+    if reports
+        .iter()
+        .any(|report| !report.disagreements.is_empty())
+    {
+        return Ok(EXIT_RUNTIMES_DISAGREED);
+    }
+    if reports.iter().any(|report| report.outcome.has_errors()) {
+        return Ok(EXIT_VALIDATION_ERRORS);
     }
+    if args.fail_on_warnings && reports.iter().any(|report| report.outcome.has_warnings()) {
+        return Ok(EXIT_VALIDATION_WARNINGS);
+    }
+    Ok(EXIT_SUCCESS)
 }
 
-/// Define all of the host functions that the module can call
-fn create_linker(
-    engine: &wasmtime::Engine,
-    module: &wasmtime::Module,
-) -> wasmtime::Linker<Context> {
-    let mut linker = wasmtime::Linker::new(engine);
-
-    // reso.data – fill the provided buffer with UTF-8-encoded JSON data. If there is more data
-    // than the module has room for, do nothing and just return the size of the JSON data.
-    linker
-        .func_wrap(
-            "reso",
-            "data",
-            |mut caller: wasmtime::Caller<'_, Context>,
-             len: i32,
-             ptr: i32|
-             -> wasmtime::Result<i32> {
-                let Some(memory) = caller
-                    .get_export("memory")
-                    .and_then(|memory| memory.into_memory())
-                else {
-                    anyhow::bail!("No memory export");
-                };
+/// Compile the module and validate every listing with it, printing human output as it goes
+fn validate_listings(args: &Args) -> Result<Vec<ListingReport>, Error> {
+    let webassembly = args.webassembly.as_deref().expect("required by clap");
+    let listings = load_listings(args)?;
+    if (args.record.is_some() || args.write_data.is_some()) && listings.len() != 1 {
+        return Err(Error::Usage(
+            "--record and --write-data can only be used with a single listing".to_string(),
+        ));
+    }
 
-                let (memory, context) = memory.data_and_store_mut(&mut caller);
-                let memory = read_slice_mut(memory, len, ptr, "data")?;
+    let schema = read_schema(args)?;
+    let check_schema = |data: &serde_json::Value| match &schema {
+        Some(schema) => schema.check(data),
+        None => Vec::new(),
+    };
 
-                let data_len = context.data.len();
-                if data_len > memory.len() {
-                    log_call!(context, "(reso.data len:{len} ptr:{ptr}) → {data_len}");
-                    return Ok(data_len as i32);
-                }
+    let previous_data = match &args.previous_data {
+        Some(path) => read_previous_data(path, schema.as_ref())?,
+        None => serde_json::Value::Null,
+    };
+    let previous_data = serde_json::to_string(&previous_data).unwrap();
+    let history = args.history.as_deref().map(History::open).transpose()?;
 
-                let memory = &mut memory[..data_len];
-                memory.copy_from_slice(context.data.as_bytes());
-
-                log_call!(context, "(reso.data len:{len} ptr:{ptr}) → {data_len}");
-                Ok(data_len as i32)
-            },
-        )
-        .unwrap();
-
-    // reso.previous_data – same as reso.data but with the previous data instead.
-    linker
-        .func_wrap(
-            "reso",
-            "previous_data",
-            |mut caller: wasmtime::Caller<'_, Context>,
-             len: i32,
-             ptr: i32|
-             -> wasmtime::Result<i32> {
-                let Some(memory) = caller
-                    .get_export("memory")
-                    .and_then(|memory| memory.into_memory())
-                else {
-                    anyhow::bail!("No memory export");
-                };
+    let related = read_related(args, schema.as_ref())?;
+    let catalog = read_catalog(args)?;
 
-                let (memory, context) = memory.data_and_store_mut(&mut caller);
-                let memory = read_slice_mut(memory, len, ptr, "previous_data")?;
+    let contents = read_module(args, webassembly)?;
+    let validator = match args.coverage {
+        Some(_) => {
+            let instrumented = instrument_module(webassembly, &contents)?;
+            Validator::from_bytes(args.runtime, &instrumented, args.stats.is_some())?
+        }
+        None => {
+            Validator::from_contents(args.runtime, webassembly, &contents, args.stats.is_some())?
+        }
+    };
+    let mut coverage = match args.coverage {
+        Some(_) => Some(Coverage::new(
+            Metadata::from_contents(webassembly, &contents)?.as_ref(),
+        )),
+        None => None,
+    };
+    let overlays = read_overlays(args)?;
+    let module = webassembly.to_string_lossy();
+    // In a differential run, every listing is run a second time with the interpreter.
+    #[cfg(feature = "wasmi")]
+    let reference = match args.differential {
+        true => Some(Validator::from_contents(
+            Runtime::Wasmi,
+            webassembly,
+            &contents,
+            false,
+        )?),
+        false => None,
+    };
+    #[cfg(not(feature = "wasmi"))]
+    let reference: Option<Validator> = None;
+
+    let mut reports = Vec::with_capacity(listings.len());
+    let many = listings.len() > 1;
+    for (listing, mut data) in listings {
+        let key = listing_key(&data);
+        let from_history = match (&history, &key) {
+            (Some(history), Some(key)) => history
+                .previous(key)?
+                .map(|previous_data| (history.path(key), previous_data)),
+            _ => None,
+        };
 
-                let previous_data_len = context.previous_data.len();
-                if previous_data_len > memory.len() {
-                    log_call!(
-                        context,
-                        "(reso.previous_data len:{len} ptr:{ptr}) → {previous_data_len}"
-                    );
-                    return Ok(previous_data_len as i32);
+        // A listing of the wrong types isn't run, but the rest of the batch still is. The same
+        // goes for a listing whose previous version in the history is of the wrong types.
+        let mismatched = std::iter::once((PathBuf::from(&listing), &data))
+            .chain(
+                from_history
+                    .as_ref()
+                    .map(|(path, previous_data)| (path.clone(), previous_data)),
+            )
+            .map(|(path, data)| (path, check_schema(data)))
+            .find(|(_, mismatches)| !mismatches.is_empty());
+        if let Some((path, mismatches)) = mismatched {
+            if args.format == Format::Human && many {
+                println!("📄 {listing}");
+            }
+            eprintln!(
+                "{}",
+                Error::Schema {
+                    path,
+                    mismatches: mismatches.clone()
                 }
-
-                let memory = &mut memory[..previous_data_len];
-                memory.copy_from_slice(context.previous_data.as_bytes());
-
-                log_call!(
-                    context,
-                    "(reso.previous_data len:{len} ptr:{ptr}) → {previous_data_len}"
-                );
-                Ok(previous_data_len as i32)
-            },
-        )
-        .unwrap();
-
-    // reso.error – the field (as specified as a UTF-8 string of length `field_len` that starts in
-    // memory at `field_ptr`) is invalid. The reason is provided in the message (as specified as a
-    // UTF-8 string of length `message_len` that starts in memory at `message_ptr`).
-    linker.func_wrap(
-        "reso",
-        "error",
-        |mut caller: wasmtime::Caller<'_, Context>,
-         field_len: i32,
-         field_ptr: i32,
-         message_len: i32,
-         message_ptr: i32|
-         -> wasmtime::Result<()> {
-            let Some(memory) = caller
-                .get_export("memory")
-                .and_then(|memory| memory.into_memory())
-            else {
-                anyhow::bail!("No memory export");
-            };
-
-            let memory = memory.data(&caller);
-            let context = caller.data();
-
-            let field = read_string(memory, field_len, field_ptr, "field")?;
-            let message = read_string(memory, message_len, message_ptr, "message")?;
-
-            log_call!(
-                context,
-                "(reso.error field_len:{field_len} field_ptr:{field_ptr} message_len:{message_len} message_ptr:{message_ptr})"
             );
-            println!("❗️ {field}: {message}");
-
-            Ok(())
-        },
-    ).unwrap();
-
-    // reso.warn – the field has a warning. The reason is provided in the message.
-    linker.func_wrap(
-        "reso",
-        "warn",
-        |mut caller: wasmtime::Caller<'_, Context>,
-         field_len: i32,
-         field_ptr: i32,
-         message_len: i32,
-         message_ptr: i32|
-         -> wasmtime::Result<()> {
-            let Some(memory) = caller
-                .get_export("memory")
-                .and_then(|memory| memory.into_memory())
-            else {
-                anyhow::bail!("No memory export");
-            };
-
-            let memory = memory.data(&caller);
-            let context = caller.data();
+            reports.push(ListingReport {
+                listing,
+                outcome: Default::default(),
+                trap: None,
+                trap_cause: None,
+                stats: None,
+                disagreements: Vec::new(),
+                schema_mismatches: mismatches,
+            });
+            continue;
+        }
 
-            let field = read_string(memory, field_len, field_ptr, "field")?;
-            let message = read_string(memory, message_len, message_ptr, "message")?;
+        let previous_data = match from_history {
+            Some((_, previous_data)) => {
+                if args.verbose > 0 {
+                    let key = key.as_deref().unwrap_or_default();
+                    println!("🗂️ Previous data for {key} taken from the history");
+                }
+                serde_json::to_string(&previous_data).unwrap()
+            }
+            None => previous_data.clone(),
+        };
 
-            log_call!(
-                context,
-                "(reso.warn field_len:{field_len} field_ptr:{field_ptr} message_len:{message_len} message_ptr:{message_ptr})"
-            );
-            println!("⚠️ {field}: {message}");
-
-            Ok(())
-        },
-    ).unwrap();
-
-    // reso.diagnostic – a way for modules to output information. Takes a single string (represented
-    // by a len+address pair).
-    linker
-        .func_wrap(
-            "reso",
-            "diagnostic",
-            |mut caller: wasmtime::Caller<'_, Context>,
-             len: i32,
-             ptr: i32|
-             -> wasmtime::Result<()> {
-                let Some(memory) = caller
-                    .get_export("memory")
-                    .and_then(|memory| memory.into_memory())
-                else {
-                    anyhow::bail!("No memory export");
-                };
+        // Build up a context based on the arguments
+        let context = || {
+            let context = Context::new(
+                serde_json::to_string(&data).unwrap(),
+                previous_data.clone(),
+                args.verbose,
+            )
+            .with_related(expanded_collections(&data))
+            .with_related(related.clone())
+            .with_messages(catalog.clone())
+            .with_quotas(quotas(args));
+            match &args.edited {
+                Some(edited) => context.with_edited(edited.clone()),
+                None => context,
+            }
+        };
+        let mut primary = context();
+        if args.record.is_some() {
+            primary = primary.recording();
+        }
+        if args.stats.is_some() {
+            primary = primary.measuring();
+        }
 
-                let memory = memory.data(&caller);
-                let context = caller.data();
+        let mut finished = run_layers(&module, &validator, &overlays, primary, context)?;
+        if let Some(coverage) = &mut coverage {
+            coverage.record(&finished.coverage);
+        }
+        let disagreements = match &reference {
+            Some(reference) => disagreements(&validator, &finished, reference, context())?,
+            None => Vec::new(),
+        };
+        let mut report = ListingReport {
+            listing,
+            outcome: std::mem::take(&mut finished.context.outcome),
+            trap: finished.trap.as_ref().map(|trap| format!("{trap:#}")),
+            trap_cause: finished
+                .trap
+                .as_ref()
+                .map(|trap| trap.root_cause().to_string()),
+            stats: finished.context.stats.take(),
+            disagreements,
+            schema_mismatches: Vec::new(),
+        };
 
-                log_call!(context, "(reso.diagnostic len:{len} ptr:{ptr})");
-                let diagnostic = read_string_lax(memory, len, ptr, "diagnostic")?;
+        // Only listings that would have been accepted become the previous data for the next run.
+        if let (Some(history), Some(key)) = (&history, &key) {
+            if report.trap.is_none() && !report.outcome.has_errors() {
+                history.record(key, &data)?;
+            }
+        }
 
-                // log_call!(context, "(reso.diagnostic len:{len} ptr:{ptr})");
-                if context.verbose > 0 {
-                    println!("ℹ️  {diagnostic}");
-                }
+        if let Some(record) = &args.record {
+            Trace::new(finished.context, finished.trap.as_ref()).to_file(record)?;
+        }
 
-                Ok(())
-            },
-        )
-        .unwrap();
-
-    // reso.set_required – set whether the field (len+address) is required (0 is not required, any
-    // other value is required)
-    linker
-        .func_wrap(
-            "reso",
-            "set_required",
-            |mut caller: wasmtime::Caller<'_, Context>,
-             len: i32,
-             ptr: i32,
-             value: i32|
-             -> wasmtime::Result<()> {
-                let Some(memory) = caller
-                    .get_export("memory")
-                    .and_then(|memory| memory.into_memory())
-                else {
-                    anyhow::bail!("No memory export");
-                };
+        if let Some(write_data) = &args.write_data {
+            for problem in report.outcome.apply(&mut data) {
+                eprintln!("Failed to apply {problem}");
+            }
+            let contents = serde_json::to_vec_pretty(&data).unwrap();
+            std::fs::write(write_data, contents).map_err(|source| Error::WriteOutput {
+                path: write_data.clone(),
+                source,
+            })?;
+        }
 
-                let memory = memory.data(&caller);
-                let context = caller.data();
+        // Narrowing the report comes last, so the history and written data still see everything.
+        if let Some(fields) = &args.fields {
+            report.outcome.retain_fields(fields);
+        }
 
-                let field = read_string(memory, len, ptr, "field")?;
+        if args.format == Format::Human {
+            if many {
+                println!("📄 {}", report.listing);
+            }
+            report.outcome.print_human();
+            match (args.stats, &report.stats) {
+                (Some(StatsFormat::Human), Some(stats)) => stats.print_human(),
+                (Some(StatsFormat::Json), Some(stats)) => {
+                    println!("{}", serde_json::to_string_pretty(stats).unwrap())
+                }
+                _ => {}
+            }
+            if let Some(trap) = finished.trap {
+                eprintln!("{}", Error::Trap(trap));
+            }
+        }
+        for disagreement in &report.disagreements {
+            eprintln!("⚖️  {}: {disagreement}", report.listing);
+        }
 
-                log_call!(
-                    context,
-                    "(reso.set_required len:{len} ptr:{ptr} value:{value})"
-                );
-                println!(
-                    "💬 {field} is \x1b[35m{}\x1b[0m",
-                    if value == 0 {
-                        "not required"
-                    } else {
-                        "required"
-                    }
-                );
+        reports.push(report);
+    }
 
-                Ok(())
-            },
-        )
-        .unwrap();
-
-    // reso.set_display – set whether the field (len+address) should be displayed (0 is do not
-    // display, any other value is yes display the field)
-    linker
-        .func_wrap(
-            "reso",
-            "set_display",
-            |mut caller: wasmtime::Caller<'_, Context>,
-             len: i32,
-             ptr: i32,
-             value: i32|
-             -> wasmtime::Result<()> {
-                let Some(memory) = caller
-                    .get_export("memory")
-                    .and_then(|memory| memory.into_memory())
-                else {
-                    anyhow::bail!("No memory export");
-                };
+    if let (Some(format), Some(coverage)) = (args.coverage, &coverage) {
+        match (format, args.format) {
+            (StatsFormat::Human, Format::Human) => print!("{coverage}"),
+            (StatsFormat::Human, _) => eprint!("{coverage}"),
+            (StatsFormat::Json, Format::Human) => {
+                println!("{}", serde_json::to_string_pretty(coverage).unwrap())
+            }
+            (StatsFormat::Json, _) => {
+                eprintln!("{}", serde_json::to_string_pretty(coverage).unwrap())
+            }
+        }
+    }
+    Ok(reports)
+}
 
-                let memory = memory.data(&caller);
-                let context = caller.data();
+/// Run a listing with the module and then every overlay, layering their outcomes
+///
+/// The first context is for the module itself, named `module`; every overlay gets a fresh one
+/// from `context`.
+fn run_layers(
+    module: &str,
+    validator: &Validator,
+    overlays: &[(String, Validator)],
+    first: Context,
+    context: impl Fn() -> Context,
+) -> Result<Finished, Error> {
+    let mut finished = validator.run(first)?;
+    if overlays.is_empty() {
+        return Ok(finished);
+    }
 
-                let field = read_string(memory, len, ptr, "field")?;
+    finished.context.outcome.tag(module);
+    if let Some(trap) = finished.trap.take() {
+        finished.trap = Some(trap.context(format!("in {module}")));
+        return Ok(finished);
+    }
 
-                log_call!(
-                    context,
-                    "(reso.set_visible len:{len} ptr:{ptr} value:{value})"
-                );
-                println!(
-                    "💬 {field} is \x1b[35m{}\x1b[0m",
-                    if value == 0 { "not visible" } else { "visible" }
-                );
+    for (module, overlay) in overlays {
+        let layer = overlay.run(context())?;
+        finished
+            .context
+            .outcome
+            .overlay(module, layer.context.outcome);
+        if let Some(trap) = layer.trap {
+            finished.trap = Some(trap.context(format!("in {module}")));
+            break;
+        }
+    }
+    Ok(finished)
+}
 
-                Ok(())
-            },
-        )
-        .unwrap();
-
-    // reso.set – set a field to the provided value. The field is provided as a len+addr pair. The
-    // value is provided as a len+addr pair that is expected to be JSON data.
-    linker
-        .func_wrap(
-            "reso",
-            "set",
-            |mut caller: wasmtime::Caller<'_, Context>,
-             field_len: i32,
-             field_ptr: i32,
-             value_len: i32,
-             value_ptr: i32|
-             -> wasmtime::Result<()> {
-                let Some(memory) = caller
-                    .get_export("memory")
-                    .and_then(|memory| memory.into_memory())
-                else {
-                    anyhow::bail!("No memory export");
+/// Run a listing with a second runtime and describe every way its outcome differs from the first
+fn disagreements(
+    validator: &Validator,
+    finished: &Finished,
+    reference: &Validator,
+    context: Context,
+) -> Result<Vec<String>, Error> {
+    let expected = reference.run(context)?;
+    let (ours, theirs) = (validator.runtime(), reference.runtime());
+
+    let mut disagreements = Vec::new();
+    let changes = finished
+        .context
+        .outcome
+        .changes_since(&expected.context.outcome);
+    for change in changes {
+        disagreements.push(match change {
+            Change::Appeared {
+                field,
+                severity,
+                message,
+            } => format!(
+                "{field}: only {ours} reported the {severity} {:?}",
+                message.message
+            ),
+            Change::Cleared {
+                field,
+                severity,
+                message,
+            } => format!(
+                "{field}: only {theirs} reported the {severity} {:?}",
+                message.message
+            ),
+            Change::Changed {
+                field,
+                setting,
+                before,
+                after,
+            } => {
+                let describe = |value: Option<serde_json::Value>| {
+                    value.map_or("nothing".to_string(), |value| value.to_string())
                 };
+                format!(
+                    "{field}: {ours} set {setting} to {} but {theirs} set it to {}",
+                    describe(after),
+                    describe(before)
+                )
+            }
+        });
+    }
+    if finished.context.outcome.host_warnings != expected.context.outcome.host_warnings {
+        disagreements.push(format!("{ours} and {theirs} gave different host warnings"));
+    }
+    match (&finished.trap, &expected.trap) {
+        (Some(trap), None) => disagreements.push(format!("only {ours} trapped: {trap:#}")),
+        (None, Some(trap)) => disagreements.push(format!("only {theirs} trapped: {trap:#}")),
+        _ => {}
+    }
+    Ok(disagreements)
+}
 
-                let memory = memory.data(&caller);
-                let context = caller.data();
+/// Parse a `--related NAME=FILE` argument
+fn parse_related(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+            Ok((name.to_string(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected NAME=FILE, got {value:?}")),
+    }
+}
 
-                let field = read_string(memory, field_len, field_ptr, "field")?;
-                let value = read_string(memory, value_len, value_ptr, "field")?;
-                let Ok(value) = serde_json::from_str::<serde_json::Value>(value) else {
-                    anyhow::bail!("value was not a valid JSON value");
-                };
+/// Build the schema to check data against, if `--check-schema` or `--schema` was given
+fn read_schema(args: &Args) -> Result<Option<Schema>, Error> {
+    match (&args.schema, args.check_schema) {
+        (Some(path), _) => {
+            let mut schema = Schema::data_dictionary();
+            schema.extend_from_file(path)?;
+            Ok(Some(schema))
+        }
+        (None, true) => Ok(Some(Schema::data_dictionary())),
+        (None, false) => Ok(None),
+    }
+}
 
-                log_call!(
-                    context,
-                    "(reso.set field_len:{field_len} field_ptr:{field_ptr} value_len:{value_len} value_ptr:{value_ptr})"
-                );
-                println!(
-                    "✏️  {field} set to \x1b[36m{}\x1b[0m",
-                    serde_json::to_string(&value).unwrap(),
-                );
+/// Read a module, checking its signature if `--trusted-keys` was given
+///
+/// Compile the contents this returns rather than reading the file again, so that what runs is
+/// what was verified.
+fn read_module(args: &Args, webassembly: &Path) -> Result<Vec<u8>, Error> {
+    let contents = std::fs::read(webassembly).map_err(|source| Error::ReadInput {
+        path: webassembly.to_path_buf(),
+        source,
+    })?;
+    let Some(trusted_keys) = &args.trusted_keys else {
+        return Ok(contents);
+    };
+    let trusted_keys = TrustedKeys::from_file(trusted_keys)?;
+    match signature::verify(&contents, &trusted_keys) {
+        Ok(signer) if args.verbose > 0 => println!("🔏 Module signed by {signer}"),
+        Ok(_) => {}
+        Err(problem) => {
+            let err = Error::Unverified {
+                path: webassembly.to_path_buf(),
+                problem,
+            };
+            match args.allow_unverified {
+                true => eprintln!("🔓 {err}; running it anyway"),
+                false => return Err(err),
+            }
+        }
+    }
+    Ok(contents)
+}
 
-                Ok(())
-            },
-        )
-        .unwrap();
+/// Add function coverage counters to the module, for `--coverage`
+///
+/// Text modules are assembled first, since the counters are added to the binary format.
+fn instrument_module(webassembly: &Path, contents: &[u8]) -> Result<Vec<u8>, Error> {
+    wat::parse_bytes(contents)
+        .map_err(anyhow::Error::from)
+        .and_then(|binary| coverage::instrument(&binary))
+        .map_err(|source| Error::Compile {
+            path: webassembly.to_path_buf(),
+            source,
+        })
+}
 
-    // Any other import is allowed, but won't do anything useful. This is required because some
-    // languages implicitly assume that wasm is compiled as wasi, and provide imports for wasi, even
-    // if the module never calls them.
-    linker.define_unknown_imports_as_traps(module).unwrap();
+/// Verify and compile the modules given with `--overlay`, named after their paths
+fn read_overlays(args: &Args) -> Result<Vec<(String, Validator)>, Error> {
+    let mut overlays = Vec::with_capacity(args.overlay.len());
+    for path in &args.overlay {
+        let contents = read_module(args, path)?;
+        let validator = Validator::from_contents(args.runtime, path, &contents, false)?;
+        overlays.push((path.to_string_lossy().into_owned(), validator));
+    }
+    Ok(overlays)
+}
 
-    linker
+/// Read the previous data given with `--previous-data`, checking it against the schema if there
+/// is one
+fn read_previous_data(path: &Path, schema: Option<&Schema>) -> Result<serde_json::Value, Error> {
+    let previous_data = read_json(path)?;
+    let mismatches = schema.map_or_else(Vec::new, |schema| schema.check(&previous_data));
+    match mismatches.is_empty() {
+        true => Ok(previous_data),
+        false => Err(Error::Schema {
+            path: path.to_path_buf(),
+            mismatches,
+        }),
+    }
 }
 
-/// Read a string from the WebAssembly module's memory
+/// Read the related resources given with `--related`, stringified and keyed by name
 ///
-/// If it doesn't happen to be UTF-8, that's fine; do our best.
-fn read_string_lax<'a>(
-    memory: &'a [u8],
-    len: i32,
-    ptr: i32,
-    name: &str,
-) -> wasmtime::Result<Cow<'a, str>> {
-    let slice = read_slice(memory, len, ptr, name)?;
-
-    Ok(String::from_utf8_lossy(slice))
+/// With a schema, each resource has to match it as the listing's collection of the same name.
+fn read_related(args: &Args, schema: Option<&Schema>) -> Result<BTreeMap<String, String>, Error> {
+    let mut related = BTreeMap::new();
+    for (name, path) in &args.related {
+        let resource = read_json(path)?;
+        if let Some(schema) = schema {
+            let mismatches = schema.check_related(name, &resource);
+            if !mismatches.is_empty() {
+                let path = path.clone();
+                return Err(Error::Schema { path, mismatches });
+            }
+        }
+        related.insert(name.clone(), serde_json::to_string(&resource).unwrap());
+    }
+    Ok(related)
 }
 
-/// Read a string from the WebAssembly module's memory
-///
-/// Fail if it isn't UTF-8.
-fn read_string<'a>(memory: &'a [u8], len: i32, ptr: i32, name: &str) -> wasmtime::Result<&'a str> {
-    let slice = read_slice(memory, len, ptr, name)?;
+/// Read the message catalog for the chosen locale, if `--messages` was given
+fn read_catalog(args: &Args) -> Result<Catalog, Error> {
+    match &args.messages {
+        Some(messages) => Catalog::load(messages, &args.locale),
+        None => Ok(Catalog::default()),
+    }
+}
 
-    match std::str::from_utf8(slice) {
-        Ok(str) => Ok(str),
-        Err(_err) => anyhow::bail!("{name} is invalid UTF-8"),
+/// The quotas the module runs under, from `--max-calls` and friends
+fn quotas(args: &Args) -> Quotas {
+    Quotas {
+        calls_per_function: args.max_calls,
+        message_bytes: args.max_message_bytes,
+        message_len: args.max_message_len,
     }
 }
 
-/// Read a slice from a WebAssembly module's memory
+/// Load every listing to validate, with the name to report it under
 ///
-/// Fail if the length or pointer are invalid.
-fn read_slice<'a>(memory: &'a [u8], len: i32, ptr: i32, name: &str) -> wasmtime::Result<&'a [u8]> {
-    if len < 0 {
-        anyhow::bail!("{name} length is less than zero");
-    }
-    if ptr < 0 {
-        anyhow::bail!("{name} pointer is less than zero");
+/// Bare listing files are named after their path. Listings from OData collections, whether in a
+/// file or fetched with `--odata-url`, are named after their `ListingKey`.
+fn load_listings(args: &Args) -> Result<Vec<(String, serde_json::Value)>, Error> {
+    let mut listings = Vec::new();
+    for path in find_listings(&args.data)? {
+        let name = path.to_string_lossy().into_owned();
+        match odata::Page::from_json(read_json(&path)?) {
+            Ok(page) => {
+                if let Some(next_link) = page.next_link {
+                    eprintln!("⏭️  {name} continues at {next_link}; only this page is validated");
+                }
+                let entities = page.entities.into_iter().enumerate();
+                listings.extend(
+                    entities.map(|(i, entity)| (odata::entity_name(&entity, &name, i), entity)),
+                );
+            }
+            Err(listing) => listings.push((name, listing)),
+        }
     }
-    let ptr = ptr as usize;
-    let len = len as usize;
 
-    let memory = &memory[ptr..];
-    if memory.len() < len {
-        anyhow::bail!("{name} length is invalid");
+    if let Some(url) = &args.odata_url {
+        let entities = odata::fetch(url, args.odata_token.as_deref())?;
+        let entities = entities.into_iter().enumerate();
+        listings.extend(entities.map(|(i, entity)| (odata::entity_name(&entity, url, i), entity)));
     }
-
-    Ok(&memory[..len])
+    Ok(listings)
 }
 
-/// Read a slice from a WebAssembly module's memory
+/// Expand the `--data` arguments into a list of listing files
 ///
-/// Fail if the length or pointer are invalid.
-fn read_slice_mut<'a>(
-    memory: &'a mut [u8],
-    len: i32,
-    ptr: i32,
-    name: &str,
-) -> wasmtime::Result<&'a mut [u8]> {
-    if len < 0 {
-        anyhow::bail!("{name} length is less than zero");
+/// Files are used as-is. Directories contribute every `.json` file directly inside them, in name
+/// order.
+fn find_listings(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+    let mut listings = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            listings.push(path.clone());
+            continue;
+        }
+
+        let entries = std::fs::read_dir(path).map_err(|source| Error::ReadInput {
+            path: path.clone(),
+            source,
+        })?;
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|source| Error::ReadInput {
+                path: path.clone(),
+                source,
+            })?;
+            let file = entry.path();
+            if file.is_file()
+                && file
+                    .extension()
+                    .is_some_and(|extension| extension == "json")
+            {
+                files.push(file);
+            }
+        }
+        files.sort();
+        listings.extend(files);
     }
-    if ptr < 0 {
-        anyhow::bail!("{name} pointer is less than zero");
+    Ok(listings)
+}
+
+/// Print a module's metadata
+fn inspect(webassembly: &Path, json: bool) -> Result<u8, Error> {
+    let metadata = Metadata::from_file(webassembly)?;
+    match (metadata, json) {
+        (Some(metadata), true) => println!("{}", serde_json::to_string_pretty(&metadata).unwrap()),
+        (None, true) => println!("null"),
+        (Some(metadata), false) => println!("{metadata}"),
+        (None, false) => println!(
+            "🤷 {} has no {} section",
+            webassembly.to_string_lossy(),
+            webassembly_rules_poc::metadata::SECTION
+        ),
     }
-    let ptr = ptr as usize;
-    let len = len as usize;
+    Ok(EXIT_SUCCESS)
+}
+
+/// Sign a module, printing the public key to add to the trusted keys
+fn sign(webassembly: &Path, key: &Path, signer: &str, output: Option<&Path>) -> Result<u8, Error> {
+    let key = signature::read_signing_key(key)?;
+    let module = std::fs::read(webassembly).map_err(|source| Error::ReadInput {
+        path: webassembly.to_path_buf(),
+        source,
+    })?;
+    let signed = signature::sign(&module, signer, &key).map_err(|problem| Error::Compile {
+        path: webassembly.to_path_buf(),
+        source: anyhow::anyhow!("{problem}"),
+    })?;
+
+    let output = output.unwrap_or(webassembly);
+    std::fs::write(output, signed).map_err(|source| Error::WriteOutput {
+        path: output.to_path_buf(),
+        source,
+    })?;
+    println!(
+        "🔏 Signed {} as {signer}; trust it with {{\"{signer}\": \"{}\"}}",
+        output.to_string_lossy(),
+        hex::encode(key.verifying_key().to_bytes())
+    );
+    Ok(EXIT_SUCCESS)
+}
 
-    let memory = &mut memory[ptr..];
-    if memory.len() < len {
-        anyhow::bail!("{name} length is invalid");
+/// Re-run the validator against a recorded trace
+fn replay(webassembly: &Path, trace: &Path, verbose: u8) -> Result<u8, Error> {
+    let recorded = Trace::from_file(trace)?;
+
+    let mut context = Context::new(
+        recorded.data.clone(),
+        recorded.previous_data.clone(),
+        verbose,
+    )
+    .with_related(recorded.related.clone())
+    .with_quotas(recorded.quotas)
+    .recording();
+    if let Some(edited) = &recorded.edited {
+        context = context.with_edited(edited.clone());
+    }
+    let finished = execute(webassembly, context)?;
+    let replayed = Trace::new(finished.context, finished.trap.as_ref());
+
+    let divergences = recorded.divergences(&replayed);
+    if divergences.is_empty() {
+        println!(
+            "✅ Replayed {} host calls recorded by {} with no divergence",
+            recorded.calls.len(),
+            recorded.host
+        );
+        return Ok(EXIT_SUCCESS);
     }
 
-    Ok(&mut memory[..len])
+    for divergence in &divergences {
+        println!("🔀 {divergence}");
+    }
+    eprintln!(
+        "Replay diverged from the trace recorded by {} in {} place(s)",
+        recorded.host,
+        divergences.len()
+    );
+    Ok(EXIT_REPLAY_DIVERGED)
 }
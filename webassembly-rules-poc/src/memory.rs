@@ -0,0 +1,224 @@
+use std::borrow::Cow;
+
+/// A region of a WebAssembly module's memory, as handed to the host in a len+ptr pair
+///
+/// Every host function goes through this to touch the module's memory. The pair is checked when
+/// the slice is made, and the region is checked against the memory whenever it's used, so a bad
+/// pointer or length becomes an error (and the module traps) instead of a panic in the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuestSlice {
+    /// What the region holds, for error messages
+    name: &'static str,
+    start: usize,
+    end: usize,
+}
+
+impl GuestSlice {
+    /// Check a len+ptr pair from the module
+    ///
+    /// Pointers are unsigned, as they are to WebAssembly, so memories over 2 GiB can be used. Fail
+    /// if the length is negative, or if the region would end past what a 32-bit memory can hold.
+    pub fn new(len: i32, ptr: i32, name: &'static str) -> anyhow::Result<Self> {
+        let Ok(len) = u32::try_from(len) else {
+            anyhow::bail!("{name} length is less than zero");
+        };
+        let ptr = ptr as u32;
+        let Some(end) = ptr.checked_add(len) else {
+            anyhow::bail!("{name} ends past the largest possible memory");
+        };
+        Ok(Self {
+            name,
+            start: ptr as usize,
+            end: end as usize,
+        })
+    }
+
+    /// The number of bytes in the region
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Whether the region is empty
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Read the region
+    ///
+    /// Fail if it doesn't fit in the module's memory.
+    pub fn bytes<'a>(&self, memory: &'a [u8]) -> anyhow::Result<&'a [u8]> {
+        self.check(memory.len())?;
+        Ok(&memory[self.start..self.end])
+    }
+
+    /// Read the region for writing
+    ///
+    /// Fail if it doesn't fit in the module's memory.
+    pub fn bytes_mut<'a>(&self, memory: &'a mut [u8]) -> anyhow::Result<&'a mut [u8]> {
+        self.check(memory.len())?;
+        Ok(&mut memory[self.start..self.end])
+    }
+
+    /// Read the region as a string
+    ///
+    /// Fail if it isn't UTF-8.
+    pub fn string<'a>(&self, memory: &'a [u8]) -> anyhow::Result<&'a str> {
+        match std::str::from_utf8(self.bytes(memory)?) {
+            Ok(str) => Ok(str),
+            Err(_err) => anyhow::bail!("{} is invalid UTF-8", self.name),
+        }
+    }
+
+    /// Read the region as a string
+    ///
+    /// If it doesn't happen to be UTF-8, that's fine; do our best.
+    pub fn string_lax<'a>(&self, memory: &'a [u8]) -> anyhow::Result<Cow<'a, str>> {
+        Ok(String::from_utf8_lossy(self.bytes(memory)?))
+    }
+
+    /// Copy `contents` to the start of the region if there's room, and return their length
+    ///
+    /// This is how host functions hand data to modules: if the buffer is too small, nothing is
+    /// written and the module can use the length to try again with a bigger one. Returns whether
+    /// the contents were written, too.
+    pub fn write_if_fits(&self, memory: &mut [u8], contents: &[u8]) -> anyhow::Result<(i32, bool)> {
+        let buffer = self.bytes_mut(memory)?;
+        let Ok(len) = i32::try_from(contents.len()) else {
+            anyhow::bail!("{} is too large to hand to a module", self.name);
+        };
+        if contents.len() > buffer.len() {
+            return Ok((len, false));
+        }
+        buffer[..contents.len()].copy_from_slice(contents);
+        Ok((len, true))
+    }
+
+    /// Fail if the region doesn't fit in a memory of the given size
+    fn check(&self, memory_len: usize) -> anyhow::Result<()> {
+        if self.start > memory_len {
+            anyhow::bail!("{} pointer is past the end of memory", self.name);
+        }
+        if self.end > memory_len {
+            anyhow::bail!("{} length is invalid", self.name);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GuestSlice;
+
+    fn error(result: anyhow::Result<impl std::fmt::Debug>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn negative_lengths_are_rejected() {
+        assert_eq!(
+            error(GuestSlice::new(-1, 0, "field")),
+            "field length is less than zero"
+        );
+    }
+
+    #[test]
+    fn pointers_are_unsigned() {
+        let slice = GuestSlice::new(1, i32::MIN, "field").unwrap();
+        assert_eq!(slice.start, 1 << 31);
+        assert_eq!(slice.len(), 1);
+    }
+
+    #[test]
+    fn regions_ending_past_32_bits_are_rejected() {
+        assert!(GuestSlice::new(0, -1, "field").is_ok());
+        assert_eq!(
+            error(GuestSlice::new(1, -1, "field")),
+            "field ends past the largest possible memory"
+        );
+        assert_eq!(
+            error(GuestSlice::new(i32::MAX, i32::MIN + 1, "field")),
+            "field ends past the largest possible memory"
+        );
+        assert!(GuestSlice::new(i32::MAX, i32::MIN, "field").is_ok());
+    }
+
+    #[test]
+    fn pointers_past_the_end_of_memory_are_errors() {
+        let memory = [0; 16];
+        let slice = GuestSlice::new(0, 17, "field").unwrap();
+        assert_eq!(
+            error(slice.bytes(&memory)),
+            "field pointer is past the end of memory"
+        );
+        let slice = GuestSlice::new(1, -2, "field").unwrap();
+        assert_eq!(
+            error(slice.bytes(&memory)),
+            "field pointer is past the end of memory"
+        );
+    }
+
+    #[test]
+    fn lengths_past_the_end_of_memory_are_errors() {
+        let mut memory = [0; 16];
+        let slice = GuestSlice::new(2, 15, "field").unwrap();
+        assert_eq!(error(slice.bytes(&memory)), "field length is invalid");
+        assert_eq!(
+            error(slice.bytes_mut(&mut memory)),
+            "field length is invalid"
+        );
+        let slice = GuestSlice::new(i32::MAX, 1, "field").unwrap();
+        assert_eq!(error(slice.bytes(&memory)), "field length is invalid");
+    }
+
+    #[test]
+    fn regions_at_the_edges_of_memory_are_fine() {
+        let memory: Vec<u8> = (0..16).collect();
+        let read = |len, ptr| {
+            GuestSlice::new(len, ptr, "field")
+                .unwrap()
+                .bytes(&memory)
+                .unwrap()
+        };
+        assert_eq!(read(0, 0), &[] as &[u8]);
+        assert_eq!(read(0, 16), &[] as &[u8]);
+        assert_eq!(read(16, 0), &memory[..]);
+        assert_eq!(read(1, 15), &[15]);
+        assert!(GuestSlice::new(0, 0, "field").unwrap().bytes(&[]).is_ok());
+    }
+
+    #[test]
+    fn strings_must_be_utf8_unless_lax() {
+        let memory = b"ok\xff";
+        let valid = GuestSlice::new(2, 0, "field").unwrap();
+        let invalid = GuestSlice::new(3, 0, "field").unwrap();
+        assert_eq!(valid.string(memory).unwrap(), "ok");
+        assert_eq!(error(invalid.string(memory)), "field is invalid UTF-8");
+        assert_eq!(invalid.string_lax(memory).unwrap(), "ok\u{fffd}");
+    }
+
+    #[test]
+    fn contents_are_only_written_if_they_fit() {
+        let mut memory = [0; 8];
+        let buffer = GuestSlice::new(4, 2, "data").unwrap();
+        assert_eq!(
+            buffer.write_if_fits(&mut memory, b"12345").unwrap(),
+            (5, false)
+        );
+        assert_eq!(memory, [0; 8]);
+        assert_eq!(
+            buffer.write_if_fits(&mut memory, b"123").unwrap(),
+            (3, true)
+        );
+        assert_eq!(memory, [0, 0, b'1', b'2', b'3', 0, 0, 0]);
+    }
+
+    #[test]
+    fn writing_to_a_bad_buffer_is_an_error_even_with_nothing_to_write() {
+        let mut memory = [0; 8];
+        let buffer = GuestSlice::new(4, 6, "data").unwrap();
+        assert_eq!(
+            error(buffer.write_if_fits(&mut memory, b"")),
+            "data length is invalid"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::Error;
+use std::{collections::BTreeMap, path::Path};
+
+/// Message templates for a locale, used to render `reso.error_code` and `reso.warn_code` calls
+///
+/// A catalog is a directory of JSON files named after locales (`en.json`, `es.json`,
+/// `es-MX.json`), each mapping message keys to templates. Templates refer to parameters by name,
+/// as in `"List price must be at least {min}"`.
+#[derive(Debug, Default, Clone)]
+pub struct Catalog {
+    /// Templates for each locale we fall back through, most specific first
+    templates: Vec<BTreeMap<String, String>>,
+}
+
+impl Catalog {
+    /// Load the templates for `locale` from the catalog directory
+    ///
+    /// A regional locale like `es-MX` falls back to its language (`es`). Locales without a file
+    /// are skipped, so an untranslated locale simply renders every message as its key.
+    pub fn load(dir: &Path, locale: &str) -> Result<Self, Error> {
+        let mut locales = vec![locale];
+        if let Some((language, _region)) = locale.split_once(['-', '_']) {
+            locales.push(language);
+        }
+
+        let mut templates = Vec::new();
+        for locale in locales {
+            let path = dir.join(format!("{locale}.json"));
+            if !path.is_file() {
+                continue;
+            }
+            let contents = std::fs::read(&path).map_err(|source| Error::ReadInput {
+                path: path.clone(),
+                source,
+            })?;
+            let locale_templates = serde_json::from_slice(&contents)
+                .map_err(|source| Error::ParseInput { path, source })?;
+            templates.push(locale_templates);
+        }
+
+        Ok(Self { templates })
+    }
+
+    /// Render the message for `key`, substituting `{name}` placeholders with the parameters
+    ///
+    /// Falls back to the key itself when no locale has a template for it. Placeholders without a
+    /// parameter are left as they are, and parameters are substituted in a single pass, so a value
+    /// that itself looks like `{name}` is never substituted again.
+    pub fn render(&self, key: &str, params: &serde_json::Map<String, serde_json::Value>) -> String {
+        let Some(template) = self
+            .templates
+            .iter()
+            .find_map(|templates| templates.get(key))
+        else {
+            return key.to_string();
+        };
+
+        let mut message = String::with_capacity(template.len());
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            message.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let param = rest
+                .find('}')
+                .and_then(|end| Some((params.get(&rest[1..end])?, end)));
+            match param {
+                Some((value, end)) => {
+                    match value {
+                        serde_json::Value::String(value) => message.push_str(value),
+                        value => message.push_str(&value.to_string()),
+                    }
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    message.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        message.push_str(rest);
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Catalog;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn catalog(templates: serde_json::Value) -> Catalog {
+        Catalog {
+            templates: vec![serde_json::from_value::<BTreeMap<_, _>>(templates).unwrap()],
+        }
+    }
+
+    fn params(params: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+        serde_json::from_value(params).unwrap()
+    }
+
+    #[test]
+    fn parameters_are_substituted() {
+        let catalog = catalog(json!({ "min_price": "{field} must be at least {min}" }));
+        assert_eq!(
+            catalog.render(
+                "min_price",
+                &params(json!({ "field": "ListPrice", "min": 1000 }))
+            ),
+            "ListPrice must be at least 1000"
+        );
+    }
+
+    #[test]
+    fn missing_keys_render_as_the_key() {
+        let catalog = catalog(json!({ "min_price": "too low" }));
+        assert_eq!(
+            catalog.render("max_price", &params(json!({ "max": 1 }))),
+            "max_price"
+        );
+    }
+
+    #[test]
+    fn missing_parameters_are_left_in_place() {
+        let catalog = catalog(json!({ "range": "between {min} and {max} {" }));
+        assert_eq!(
+            catalog.render("range", &params(json!({ "min": 1 }))),
+            "between 1 and {max} {"
+        );
+    }
+
+    #[test]
+    fn substituted_values_are_not_substituted_again() {
+        let catalog = catalog(json!({ "unknown": "{value} isn't one of {choices}" }));
+        assert_eq!(
+            catalog.render(
+                "unknown",
+                &params(json!({ "value": "{choices}", "choices": "A, B" }))
+            ),
+            "{choices} isn't one of A, B"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{sections, Error};
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path};
+
+/// The custom section a module's metadata is kept in
+pub const SECTION: &str = "reso.metadata";
+
+/// What a module says about itself, so a `.wasm` file isn't entirely opaque
+///
+/// Generators write it as JSON in a `reso.metadata` custom section. Every field is optional, since
+/// the host has to cope with modules from any generator.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    /// The MLS whose rules the module implements
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mls_id: Option<String>,
+    /// The version of the MLS' rules the module was built from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules_version: Option<String>,
+    /// When the module was built, as an RFC 3339 timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_timestamp: Option<String>,
+    /// The tool that built the module, and its version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>,
+    /// The RESO resources the module knows how to validate, such as `Property`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<String>,
+    /// A short description of each rule the module implements, in order, for reports such as
+    /// coverage
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<String>,
+}
+
+impl Metadata {
+    /// Read a module's metadata
+    ///
+    /// Returns `None` if the module has no metadata section, or is in the text format, which has
+    /// no custom sections.
+    pub fn from_file(webassembly: &Path) -> Result<Option<Self>, Error> {
+        let module = std::fs::read(webassembly).map_err(|source| Error::ReadInput {
+            path: webassembly.to_path_buf(),
+            source,
+        })?;
+        Self::from_contents(webassembly, &module)
+    }
+
+    /// Read the metadata of a module that was already read from the given file
+    pub fn from_contents(webassembly: &Path, module: &[u8]) -> Result<Option<Self>, Error> {
+        let Ok(Some(section)) = sections::find_custom(module, SECTION) else {
+            return Ok(None);
+        };
+        serde_json::from_slice(section)
+            .map(Some)
+            .map_err(|source| Error::ParseInput {
+                path: webassembly.to_path_buf(),
+                source,
+            })
+    }
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = "(unknown)";
+        writeln!(f, "🏢 MLS: {}", self.mls_id.as_deref().unwrap_or(unknown))?;
+        writeln!(
+            f,
+            "🏷️ Rules version: {}",
+            self.rules_version.as_deref().unwrap_or(unknown)
+        )?;
+        writeln!(
+            f,
+            "🕒 Built: {}",
+            self.build_timestamp.as_deref().unwrap_or(unknown)
+        )?;
+        writeln!(
+            f,
+            "🛠️ Generator: {}",
+            self.generator.as_deref().unwrap_or(unknown)
+        )?;
+        match self.resources.is_empty() {
+            true => write!(f, "📚 Resources: {unknown}"),
+            false => write!(f, "📚 Resources: {}", self.resources.join(", ")),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{listing_key, Error};
+use serde_json::Value;
+use std::{collections::BTreeSet, io, time::Duration};
+
+/// How long to wait for a server before giving up on a page
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One page of an OData collection response, as in `{"@odata.context": ..., "value": [...]}`
+#[derive(Debug, Default)]
+pub struct Page {
+    /// The entities on this page, usually listings
+    pub entities: Vec<Value>,
+    /// Where to fetch the next page from, if there is one
+    pub next_link: Option<String>,
+}
+
+impl Page {
+    /// Read a page from a response
+    ///
+    /// A collection is an object with a `value` array and nothing else but annotations such as
+    /// `@odata.context`, so a listing that happens to have a `value` field isn't mistaken for one.
+    /// Gives the JSON back untouched if it isn't an OData collection, such as a bare listing.
+    pub fn from_json(json: Value) -> Result<Self, Value> {
+        let Value::Object(mut object) = json else {
+            return Err(json);
+        };
+        let is_collection = matches!(object.get("value"), Some(Value::Array(_)))
+            && object
+                .keys()
+                .all(|key| key == "value" || key.starts_with('@'));
+        if !is_collection {
+            return Err(Value::Object(object));
+        }
+        let Some(Value::Array(entities)) = object.remove("value") else {
+            unreachable!("checked above");
+        };
+        let next_link = match object.remove("@odata.nextLink") {
+            Some(Value::String(next_link)) => Some(next_link),
+            _ => None,
+        };
+        Ok(Self {
+            entities,
+            next_link,
+        })
+    }
+}
+
+/// The name to report an entity under
+///
+/// That's its `ListingKey`, or where it came from and its position if it doesn't have one.
+pub fn entity_name(entity: &Value, source: &str, index: usize) -> String {
+    listing_key(entity).unwrap_or_else(|| format!("{source}[{index}]"))
+}
+
+/// Fetch every entity in a collection from a RESO Web API server
+///
+/// Follows `@odata.nextLink` from page to page until the last one. A token, if given, is sent as
+/// a bearer token with every request to the server the collection is on; next links to any other
+/// server are followed without it, so a server can't hand the token to someone else.
+pub fn fetch(url: &str, token: Option<&str>) -> Result<Vec<Value>, Error> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let token_origin = origin(url);
+    let mut entities = Vec::new();
+    let mut visited = BTreeSet::new();
+    let mut next = Some(url.to_string());
+    while let Some(url) = next.take() {
+        let fetch_error = |source| Error::Fetch {
+            url: url.clone(),
+            source,
+        };
+        // A server that links back to a page we've already seen would otherwise keep us going
+        // forever.
+        if !visited.insert(url.clone()) {
+            return Err(fetch_error(io::Error::other(
+                "the next link leads back to this page",
+            )));
+        }
+
+        let mut request = agent.get(&url).set("Accept", "application/json");
+        if let Some(token) = token {
+            if token_origin.is_some() && origin(&url) == token_origin {
+                request = request.set("Authorization", &format!("Bearer {token}"));
+            }
+        }
+        let response = request
+            .call()
+            .map_err(|err| fetch_error(io::Error::other(err)))?;
+        let json: Value = response.into_json().map_err(fetch_error)?;
+        let page = Page::from_json(json).map_err(|_| {
+            fetch_error(io::Error::other("the response is not an OData collection"))
+        })?;
+
+        entities.extend(page.entities);
+        next = match page.next_link {
+            Some(link) => Some(resolve(&url, &link).map_err(fetch_error)?),
+            None => None,
+        };
+    }
+    Ok(entities)
+}
+
+/// The scheme, host and port of a URL
+fn origin(url: &str) -> Option<url::Origin> {
+    url::Url::parse(url)
+        .ok()
+        .map(|url| url.origin())
+        .filter(url::Origin::is_tuple)
+}
+
+/// Resolve a next link, which may be relative to the page it came from
+fn resolve(base: &str, link: &str) -> Result<String, io::Error> {
+    let base = url::Url::parse(base).map_err(io::Error::other)?;
+    let link = base.join(link).map_err(io::Error::other)?;
+    Ok(link.into())
+}
@@ -0,0 +1,779 @@
+use crate::path::FieldPath;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// How serious a reported problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single error or warning about a field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Message {
+    /// The text to show the user
+    pub message: String,
+    /// The MLS rule that produced the message, if the module said
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    /// Other fields involved in the problem
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub related_fields: Vec<String>,
+    /// The module that reported the message, when several modules were layered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+}
+
+impl From<&str> for Message {
+    fn from(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            rule_id: None,
+            related_fields: Vec::new(),
+            module: None,
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.related_fields.is_empty() {
+            write!(f, " (with {})", self.related_fields.join(", "))?;
+        }
+        if let Some(rule_id) = &self.rule_id {
+            write!(f, " \x1b[90m[{rule_id}]\x1b[0m")?;
+        }
+        if let Some(module) = &self.module {
+            write!(f, " \x1b[90mfrom {module}\x1b[0m")?;
+        }
+        Ok(())
+    }
+}
+
+/// The resolved state of a single field, once the module is done with it
+///
+/// Errors and warnings accumulate. Every other setting is last-writer-wins: if the module sets it
+/// more than once, the final call is the one that counts, and when modules are layered, the last
+/// module to set it wins.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct FieldState {
+    /// Whether the field is required, if the module said
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    /// Whether the field should be displayed, if the module said
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    /// Whether the field is read-only, if the module said
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub readonly: Option<bool>,
+    /// The values the field is limited to, if the module said
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picklist: Option<Vec<serde_json::Value>>,
+    /// The value the module set the field to. `Some(Null)` means the module cleared the field.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    /// Reasons the field is invalid
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<Message>,
+    /// Warnings about the field
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Message>,
+    /// The module that decided each setting (such as `required`), when several modules were
+    /// layered
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, String>,
+}
+
+/// A difference between two outcomes for the same listing
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// An error or warning that wasn't there before
+    Appeared {
+        field: String,
+        severity: Severity,
+        message: Message,
+    },
+    /// An error or warning that went away
+    Cleared {
+        field: String,
+        severity: Severity,
+        message: Message,
+    },
+    /// A last-writer-wins setting (required, visible, readonly, picklist or value) that changed
+    Changed {
+        field: String,
+        setting: &'static str,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn icon(severity: &Severity) -> &'static str {
+            match severity {
+                Severity::Error => "❗️",
+                Severity::Warning => "⚠️",
+            }
+        }
+        fn setting(value: &Option<serde_json::Value>) -> String {
+            match value {
+                Some(value) => serde_json::to_string(value).unwrap(),
+                None => "unset".to_string(),
+            }
+        }
+
+        match self {
+            Change::Appeared {
+                field,
+                severity,
+                message,
+            } => write!(f, "\x1b[32m+\x1b[0m {} {field}: {message}", icon(severity)),
+            Change::Cleared {
+                field,
+                severity,
+                message,
+            } => write!(f, "\x1b[31m-\x1b[0m {} {field}: {message}", icon(severity)),
+            Change::Changed {
+                field,
+                setting: name,
+                before,
+                after,
+            } => write!(
+                f,
+                "\x1b[33m~\x1b[0m 💬 {field} {name}: {} → \x1b[35m{}\x1b[0m",
+                setting(before),
+                setting(after)
+            ),
+        }
+    }
+}
+
+/// Everything the module said about the listing
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Outcome {
+    /// The state of every field the module mentioned, keyed by field name
+    pub fields: BTreeMap<String, FieldState>,
+    /// Problems the host noticed with how the module reported its outcome, such as setting the
+    /// same field twice
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub host_warnings: Vec<String>,
+}
+
+impl Outcome {
+    pub fn error(&mut self, field: &str, message: impl Into<Message>) {
+        self.report(field, Severity::Error, message.into());
+    }
+
+    pub fn warn(&mut self, field: &str, message: impl Into<Message>) {
+        self.report(field, Severity::Warning, message.into());
+    }
+
+    pub fn report(&mut self, field: &str, severity: Severity, message: Message) {
+        let state = self.fields.entry(field.to_string()).or_default();
+        let (messages, kind) = match severity {
+            Severity::Error => (&mut state.errors, "error"),
+            Severity::Warning => (&mut state.warnings, "warning"),
+        };
+        if messages.contains(&message) {
+            self.host_warnings.push(format!(
+                "{field}: the {kind} {:?} was reported more than once",
+                message.message
+            ));
+            return;
+        }
+        messages.push(message);
+    }
+
+    pub fn set_required(&mut self, field: &str, required: bool) {
+        let state = self.fields.entry(field.to_string()).or_default();
+        let warning = assign(field, "required", &mut state.required, required);
+        self.host_warnings.extend(warning);
+    }
+
+    pub fn set_visible(&mut self, field: &str, visible: bool) {
+        let state = self.fields.entry(field.to_string()).or_default();
+        let warning = assign(field, "visible", &mut state.visible, visible);
+        self.host_warnings.extend(warning);
+    }
+
+    pub fn set_readonly(&mut self, field: &str, readonly: bool) {
+        let state = self.fields.entry(field.to_string()).or_default();
+        let warning = assign(field, "readonly", &mut state.readonly, readonly);
+        self.host_warnings.extend(warning);
+    }
+
+    pub fn set_picklist(&mut self, field: &str, picklist: Vec<serde_json::Value>) {
+        let state = self.fields.entry(field.to_string()).or_default();
+        let warning = assign(field, "picklist", &mut state.picklist, picklist);
+        self.host_warnings.extend(warning);
+    }
+
+    pub fn set_value(&mut self, field: &str, value: serde_json::Value) {
+        let state = self.fields.entry(field.to_string()).or_default();
+        let warning = assign(field, "value", &mut state.value, value);
+        self.host_warnings.extend(warning);
+    }
+
+    /// Apply every `reso.set` to the data, which may set values inside nested objects and arrays
+    ///
+    /// Returns a description of every value that couldn't be set.
+    pub fn apply(&self, data: &mut serde_json::Value) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (field, state) in &self.fields {
+            let Some(value) = &state.value else {
+                continue;
+            };
+            let applied = field
+                .parse::<FieldPath>()
+                .and_then(|path| path.set(data, value.clone()));
+            if let Err(problem) = applied {
+                problems.push(format!("{field}: {problem}"));
+            }
+        }
+        problems
+    }
+
+    /// Everything that changed between a previous outcome and this one
+    pub fn changes_since(&self, previous: &Outcome) -> Vec<Change> {
+        let empty = FieldState::default();
+        let mut fields = previous.fields.keys().collect::<Vec<_>>();
+        fields.extend(self.fields.keys());
+        fields.sort();
+        fields.dedup();
+
+        let mut changes = Vec::new();
+        for field in fields {
+            let before = previous.fields.get(field).unwrap_or(&empty);
+            let after = self.fields.get(field).unwrap_or(&empty);
+
+            for (severity, before, after) in [
+                (Severity::Error, &before.errors, &after.errors),
+                (Severity::Warning, &before.warnings, &after.warnings),
+            ] {
+                for message in before.iter().filter(|message| !after.contains(message)) {
+                    changes.push(Change::Cleared {
+                        field: field.clone(),
+                        severity,
+                        message: message.clone(),
+                    });
+                }
+                for message in after.iter().filter(|message| !before.contains(message)) {
+                    changes.push(Change::Appeared {
+                        field: field.clone(),
+                        severity,
+                        message: message.clone(),
+                    });
+                }
+            }
+
+            let settings = [
+                (
+                    "required",
+                    before.required.map(Into::into),
+                    after.required.map(Into::into),
+                ),
+                (
+                    "visible",
+                    before.visible.map(Into::into),
+                    after.visible.map(Into::into),
+                ),
+                (
+                    "readonly",
+                    before.readonly.map(Into::into),
+                    after.readonly.map(Into::into),
+                ),
+                (
+                    "picklist",
+                    before.picklist.clone().map(Into::into),
+                    after.picklist.clone().map(Into::into),
+                ),
+                ("value", before.value.clone(), after.value.clone()),
+            ];
+            for (setting, before, after) in settings {
+                if before != after {
+                    changes.push(Change::Changed {
+                        field: field.clone(),
+                        setting,
+                        before,
+                        after,
+                    });
+                }
+            }
+        }
+        changes
+    }
+
+    /// Tag everything in the outcome as coming from a module, before layering another on top
+    pub fn tag(&mut self, module: &str) {
+        for state in self.fields.values_mut() {
+            for message in state.errors.iter_mut().chain(&mut state.warnings) {
+                message.module = Some(module.to_string());
+            }
+            let settings = [
+                ("required", state.required.is_some()),
+                ("visible", state.visible.is_some()),
+                ("readonly", state.readonly.is_some()),
+                ("picklist", state.picklist.is_some()),
+                ("value", state.value.is_some()),
+            ];
+            for (setting, _) in settings.into_iter().filter(|(_, set)| *set) {
+                state
+                    .sources
+                    .insert(setting.to_string(), module.to_string());
+            }
+        }
+        for warning in &mut self.host_warnings {
+            *warning = format!("{module}: {warning}");
+        }
+    }
+
+    /// Layer the outcome of a later module on top of this one
+    ///
+    /// Errors and warnings from both are kept. Settings the later module made take precedence,
+    /// so an overlay can tighten or relax what the modules before it decided. Everything the
+    /// later module said is tagged with its name.
+    pub fn overlay(&mut self, module: &str, mut overlay: Outcome) {
+        overlay.tag(module);
+        for (field, state) in overlay.fields {
+            let merged = self.fields.entry(field).or_default();
+            merged.errors.extend(state.errors);
+            merged.warnings.extend(state.warnings);
+            merged.required = state.required.or(merged.required);
+            merged.visible = state.visible.or(merged.visible);
+            merged.readonly = state.readonly.or(merged.readonly);
+            merged.picklist = state.picklist.or(merged.picklist.take());
+            merged.value = state.value.or(merged.value.take());
+            merged.sources.extend(state.sources);
+        }
+        self.host_warnings.extend(overlay.host_warnings);
+    }
+
+    /// Keep only what's relevant to a subset of the fields, such as the few a form is showing
+    ///
+    /// A field is in the subset if it's one of the given fields or nested inside one, so `Rooms`
+    /// covers `Rooms[0].RoomLevel`. Errors and warnings about other fields are kept if they name
+    /// a field in the subset as related.
+    pub fn retain_fields(&mut self, subset: &[String]) {
+        let in_subset = |field: &str| {
+            subset.iter().any(|wanted| {
+                field
+                    .strip_prefix(wanted.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+            })
+        };
+        let relevant = |message: &Message| message.related_fields.iter().any(|f| in_subset(f));
+
+        self.fields.retain(|field, state| {
+            if in_subset(field) {
+                return true;
+            }
+            state.errors.retain(relevant);
+            state.warnings.retain(relevant);
+            if state.errors.is_empty() && state.warnings.is_empty() {
+                return false;
+            }
+            *state = FieldState {
+                errors: std::mem::take(&mut state.errors),
+                warnings: std::mem::take(&mut state.warnings),
+                ..FieldState::default()
+            };
+            true
+        });
+    }
+
+    /// Whether any field has an error
+    pub fn has_errors(&self) -> bool {
+        self.fields.values().any(|state| !state.errors.is_empty())
+    }
+
+    /// Whether any field has a warning, or the host had to warn about the module
+    pub fn has_warnings(&self) -> bool {
+        !self.host_warnings.is_empty()
+            || self.fields.values().any(|state| !state.warnings.is_empty())
+    }
+
+    /// Print the outcome in a form meant for people
+    pub fn print_human(&self) {
+        for (field, state) in &self.fields {
+            let from = |setting: &str| match state.sources.get(setting) {
+                Some(module) => format!(" \x1b[90mfrom {module}\x1b[0m"),
+                None => String::new(),
+            };
+            for error in &state.errors {
+                println!("❗️ {field}: {error}");
+            }
+            for warning in &state.warnings {
+                println!("⚠️ {field}: {warning}");
+            }
+            if let Some(required) = state.required {
+                println!(
+                    "💬 {field} is \x1b[35m{}\x1b[0m{}",
+                    if required { "required" } else { "not required" },
+                    from("required")
+                );
+            }
+            if let Some(visible) = state.visible {
+                println!(
+                    "💬 {field} is \x1b[35m{}\x1b[0m{}",
+                    if visible { "visible" } else { "not visible" },
+                    from("visible")
+                );
+            }
+            if let Some(readonly) = state.readonly {
+                println!(
+                    "💬 {field} is \x1b[35m{}\x1b[0m{}",
+                    if readonly { "read-only" } else { "editable" },
+                    from("readonly")
+                );
+            }
+            if let Some(picklist) = &state.picklist {
+                println!(
+                    "💬 {field} is limited to \x1b[35m{}\x1b[0m{}",
+                    serde_json::to_string(picklist).unwrap(),
+                    from("picklist")
+                );
+            }
+            if let Some(value) = &state.value {
+                println!(
+                    "✏️  {field} set to \x1b[36m{}\x1b[0m{}",
+                    serde_json::to_string(value).unwrap(),
+                    from("value")
+                );
+            }
+        }
+        for warning in &self.host_warnings {
+            println!("🔶 {warning}");
+        }
+    }
+}
+
+/// Apply a last-writer-wins setting, describing what was wrong with the call if it repeated or
+/// contradicted an earlier one.
+fn assign<T: PartialEq + Serialize>(
+    field: &str,
+    setting: &str,
+    slot: &mut Option<T>,
+    value: T,
+) -> Option<String> {
+    let warning = match slot {
+        Some(previous) if *previous == value => Some(format!(
+            "{field}: {setting} was set to {} twice",
+            serde_json::to_string(&value).unwrap()
+        )),
+        Some(previous) => Some(format!(
+            "{field}: {setting} was set to {} and then to {}; the last one wins",
+            serde_json::to_string(previous).unwrap(),
+            serde_json::to_string(&value).unwrap()
+        )),
+        None => None,
+    };
+    *slot = Some(value);
+    warning
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, Message, Outcome, Severity};
+    use serde_json::json;
+
+    #[test]
+    fn conflicting_sets_warn_and_the_last_one_wins() {
+        let mut outcome = Outcome::default();
+        outcome.set_required("ClosePrice", true);
+        outcome.set_required("ClosePrice", false);
+        outcome.set_value("ListPrice", json!(1));
+        outcome.set_value("ListPrice", json!(2));
+
+        assert_eq!(outcome.fields["ClosePrice"].required, Some(false));
+        assert_eq!(outcome.fields["ListPrice"].value, Some(json!(2)));
+        assert_eq!(
+            outcome.host_warnings,
+            [
+                "ClosePrice: required was set to true and then to false; the last one wins",
+                "ListPrice: value was set to 1 and then to 2; the last one wins",
+            ]
+        );
+    }
+
+    #[test]
+    fn repeating_a_set_warns_that_it_was_redundant() {
+        let mut outcome = Outcome::default();
+        for _ in 0..2 {
+            outcome.set_required("ClosePrice", false);
+            outcome.set_visible("ClosePrice", true);
+            outcome.set_readonly("ClosePrice", true);
+            outcome.set_picklist("ClosePrice", vec![json!("A"), json!("B")]);
+            outcome.set_value("ClosePrice", json!(null));
+        }
+
+        assert_eq!(
+            outcome.host_warnings,
+            [
+                "ClosePrice: required was set to false twice",
+                "ClosePrice: visible was set to true twice",
+                "ClosePrice: readonly was set to true twice",
+                r#"ClosePrice: picklist was set to ["A","B"] twice"#,
+                "ClosePrice: value was set to null twice",
+            ]
+        );
+        let state = &outcome.fields["ClosePrice"];
+        assert_eq!(state.required, Some(false));
+        assert_eq!(state.picklist, Some(vec![json!("A"), json!("B")]));
+        assert_eq!(state.value, Some(json!(null)));
+    }
+
+    #[test]
+    fn redundant_and_conflicting_sets_are_told_apart() {
+        let mut outcome = Outcome::default();
+        outcome.set_value("ListPrice", json!(1));
+        outcome.set_value("ListPrice", json!(1));
+        outcome.set_value("ListPrice", json!(2));
+
+        assert_eq!(outcome.fields["ListPrice"].value, Some(json!(2)));
+        assert_eq!(
+            outcome.host_warnings,
+            [
+                "ListPrice: value was set to 1 twice",
+                "ListPrice: value was set to 1 and then to 2; the last one wins",
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_messages_are_kept_once_with_a_warning() {
+        let mut outcome = Outcome::default();
+        outcome.error("ListPrice", "too low");
+        outcome.error("ListPrice", "too low");
+
+        assert_eq!(
+            outcome.fields["ListPrice"].errors,
+            [Message::from("too low")]
+        );
+        assert_eq!(
+            outcome.host_warnings,
+            [r#"ListPrice: the error "too low" was reported more than once"#]
+        );
+    }
+
+    #[test]
+    fn messages_accumulate_in_order_around_sets() {
+        let mut outcome = Outcome::default();
+        outcome.warn("ListPrice", "unusually high");
+        outcome.set_required("ListPrice", true);
+        outcome.error("ListPrice", "too high");
+        outcome.set_required("ListPrice", false);
+        outcome.warn("ListPrice", "round number");
+        outcome.error("ListPrice", "not a number");
+        outcome.set_required("ListPrice", true);
+
+        let state = &outcome.fields["ListPrice"];
+        assert_eq!(
+            state.errors,
+            [Message::from("too high"), Message::from("not a number")]
+        );
+        assert_eq!(
+            state.warnings,
+            [
+                Message::from("unusually high"),
+                Message::from("round number")
+            ]
+        );
+        assert_eq!(state.required, Some(true));
+        assert_eq!(
+            outcome.host_warnings,
+            [
+                "ListPrice: required was set to true and then to false; the last one wins",
+                "ListPrice: required was set to false and then to true; the last one wins",
+            ]
+        );
+    }
+
+    #[test]
+    fn overlaid_settings_win_and_remember_their_module() {
+        let mut base = Outcome::default();
+        base.set_required("ListPrice", true);
+        base.set_readonly("ListPrice", true);
+        base.set_value("City", json!("Springfield"));
+        base.tag("base.wasm");
+
+        let mut overlay = Outcome::default();
+        overlay.set_required("ListPrice", false);
+        overlay.set_value("City", json!(null));
+        overlay.set_visible("Country", false);
+        base.overlay("overlay.wasm", overlay);
+
+        let price = &base.fields["ListPrice"];
+        assert_eq!(price.required, Some(false));
+        assert_eq!(price.readonly, Some(true));
+        assert_eq!(price.sources["required"], "overlay.wasm");
+        assert_eq!(price.sources["readonly"], "base.wasm");
+        assert_eq!(base.fields["City"].value, Some(json!(null)));
+        assert_eq!(base.fields["City"].sources["value"], "overlay.wasm");
+        assert_eq!(base.fields["Country"].visible, Some(false));
+        assert_eq!(base.fields["Country"].sources["visible"], "overlay.wasm");
+    }
+
+    #[test]
+    fn overlaid_messages_are_kept_alongside_the_base_ones() {
+        let mut base = Outcome::default();
+        base.error("ListPrice", "too low");
+        base.warn("ListPrice", "round number");
+        base.set_required("ClosePrice", true);
+        base.set_required("ClosePrice", false);
+        base.tag("base.wasm");
+
+        let mut overlay = Outcome::default();
+        overlay.error("ListPrice", "too low");
+        overlay.error("City", "unknown");
+        overlay.set_visible("City", true);
+        overlay.set_visible("City", false);
+        base.overlay("overlay.wasm", overlay);
+
+        let from = |message: &str, module: &str| Message {
+            module: Some(module.to_string()),
+            ..Message::from(message)
+        };
+        let price = &base.fields["ListPrice"];
+        assert_eq!(
+            price.errors,
+            [
+                from("too low", "base.wasm"),
+                from("too low", "overlay.wasm")
+            ]
+        );
+        assert_eq!(price.warnings, [from("round number", "base.wasm")]);
+        assert_eq!(
+            base.fields["City"].errors,
+            [from("unknown", "overlay.wasm")]
+        );
+        assert_eq!(
+            base.host_warnings,
+            [
+                "base.wasm: ClosePrice: required was set to true and then to false; the last one wins",
+                "overlay.wasm: City: visible was set to true and then to false; the last one wins",
+            ]
+        );
+    }
+
+    #[test]
+    fn retaining_fields_keeps_them_and_everything_nested_inside() {
+        let mut outcome = Outcome::default();
+        for field in [
+            "Media",
+            "Media[0].ImageWidth",
+            "Media.Caption",
+            "MediaCount",
+            "MediaType",
+            "ListPrice",
+        ] {
+            outcome.set_required(field, true);
+        }
+        outcome.retain_fields(&["Media".to_string()]);
+
+        let fields: Vec<_> = outcome.fields.keys().map(String::as_str).collect();
+        assert_eq!(fields, ["Media", "Media.Caption", "Media[0].ImageWidth"]);
+    }
+
+    #[test]
+    fn retaining_fields_keeps_only_the_messages_about_them_elsewhere() {
+        let mut outcome = Outcome::default();
+        let related = |message: &str, fields: &[&str]| Message {
+            related_fields: fields.iter().map(|field| field.to_string()).collect(),
+            ..Message::from(message)
+        };
+        outcome.set_required("ClosePrice", true);
+        outcome.error("ClosePrice", related("above list", &["ListPrice"]));
+        outcome.error("ClosePrice", related("too early", &["CloseDate"]));
+        outcome.warn("ClosePrice", related("no photos", &["MediaCount"]));
+        outcome.warn("PhotosCount", related("doesn't match", &["Media[0].Order"]));
+        outcome.error("City", "unknown");
+        outcome.retain_fields(&["ListPrice".to_string(), "Media".to_string()]);
+
+        let close_price = &outcome.fields["ClosePrice"];
+        assert_eq!(close_price.errors, [related("above list", &["ListPrice"])]);
+        assert!(close_price.warnings.is_empty());
+        assert_eq!(close_price.required, None);
+        assert_eq!(
+            outcome.fields["PhotosCount"].warnings,
+            [related("doesn't match", &["Media[0].Order"])]
+        );
+        assert!(!outcome.fields.contains_key("City"));
+    }
+
+    #[test]
+    fn changes_since_lists_what_appeared_cleared_and_changed() {
+        let mut before = Outcome::default();
+        before.error("ListPrice", "too low");
+        before.warn("ListPrice", "round number");
+        before.set_required("ClosePrice", true);
+        before.set_value("City", json!("Springfield"));
+
+        let mut after = Outcome::default();
+        after.warn("ListPrice", "round number");
+        after.error("ListPrice", "too high");
+        after.set_required("ClosePrice", false);
+        after.set_visible("ClosePrice", true);
+        after.set_value("City", json!("Springfield"));
+
+        assert_eq!(
+            after.changes_since(&before),
+            [
+                Change::Changed {
+                    field: "ClosePrice".to_string(),
+                    setting: "required",
+                    before: Some(json!(true)),
+                    after: Some(json!(false)),
+                },
+                Change::Changed {
+                    field: "ClosePrice".to_string(),
+                    setting: "visible",
+                    before: None,
+                    after: Some(json!(true)),
+                },
+                Change::Cleared {
+                    field: "ListPrice".to_string(),
+                    severity: Severity::Error,
+                    message: Message::from("too low"),
+                },
+                Change::Appeared {
+                    field: "ListPrice".to_string(),
+                    severity: Severity::Error,
+                    message: Message::from("too high"),
+                },
+            ]
+        );
+        assert!(after.changes_since(&after).is_empty());
+    }
+
+    #[test]
+    fn applying_skips_sets_that_cannot_be_made() {
+        let mut outcome = Outcome::default();
+        outcome.set_value("Media[0].ImageWidth", json!(640));
+        outcome.set_value("Rooms[3].RoomLevel", json!("Upper"));
+        outcome.set_value("Address.Unit", json!("4B"));
+
+        let mut data = json!({ "Rooms": [] });
+        let problems = outcome.apply(&mut data);
+        assert_eq!(
+            problems,
+            [
+                "Media[0].ImageWidth: Media is not an array",
+                "Rooms[3].RoomLevel: Rooms has 0 entries, so there is no [3]",
+            ]
+        );
+        assert_eq!(data, json!({ "Rooms": [], "Address": { "Unit": "4B" } }));
+    }
+}
@@ -0,0 +1,379 @@
+use std::{fmt, str::FromStr};
+
+/// A path to a field, possibly nested inside objects and arrays
+///
+/// A path is a field name, followed by any number of `.Name` object keys and `[n]` array indexes,
+/// as in `ListPrice`, `Media[3].ImageWidth` or `Rooms[0].Features[2]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath {
+    segments: Vec<Segment>,
+}
+
+/// A single step in a [`FieldPath`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// A key in an object
+    Key(String),
+    /// An index into an array
+    Index(usize),
+}
+
+impl FieldPath {
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Check that the path makes sense for the given data
+    ///
+    /// Everything up to the last segment has to exist. The last segment may be missing from an
+    /// object, since a module is allowed to talk about fields that have no value yet, but an
+    /// array index must be in bounds.
+    pub fn check(&self, root: &serde_json::Value) -> Result<(), String> {
+        let mut current = root;
+        for (i, segment) in self.segments.iter().enumerate() {
+            let is_last = i == self.segments.len() - 1;
+            let next = match (segment, current) {
+                (Segment::Key(key), serde_json::Value::Object(object)) => object.get(key),
+                (Segment::Index(index), serde_json::Value::Array(array)) => {
+                    match array.get(*index) {
+                        Some(next) => Some(next),
+                        None => {
+                            return Err(format!(
+                                "{} has {} entries, so there is no [{index}]",
+                                self.prefix(i),
+                                array.len()
+                            ))
+                        }
+                    }
+                }
+                (Segment::Key(_), _) if i == 0 => None,
+                (Segment::Key(_), _) => return Err(format!("{} is not an object", self.prefix(i))),
+                (Segment::Index(_), _) => {
+                    return Err(format!("{} is not an array", self.prefix(i)))
+                }
+            };
+            match next {
+                Some(next) => current = next,
+                None if is_last => return Ok(()),
+                None => return Err(format!("{} does not exist", self.prefix(i + 1))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the value at the path, creating missing object keys along the way
+    ///
+    /// Arrays are never extended, so an out-of-bounds index is an error. The whole path is
+    /// checked before anything is created, so a path that can't be set leaves the data as it was.
+    pub fn set(
+        &self,
+        root: &mut serde_json::Value,
+        value: serde_json::Value,
+    ) -> Result<(), String> {
+        self.check_settable(root)?;
+
+        let mut current = root;
+        for segment in &self.segments {
+            current = match segment {
+                Segment::Key(key) => {
+                    if current.is_null() {
+                        *current = serde_json::Value::Object(Default::default());
+                    }
+                    current
+                        .as_object_mut()
+                        .expect("checked above")
+                        .entry(key.clone())
+                        .or_insert(serde_json::Value::Null)
+                }
+                Segment::Index(index) => &mut current[*index],
+            };
+        }
+        *current = value;
+        Ok(())
+    }
+
+    /// Check that [`FieldPath::set`] can set the path without changing anything
+    ///
+    /// Missing and null values along the way would become objects, so only a key can follow them.
+    fn check_settable(&self, root: &serde_json::Value) -> Result<(), String> {
+        // `None` is a value that doesn't exist yet.
+        let mut current = Some(root);
+        for (i, segment) in self.segments.iter().enumerate() {
+            current = match (segment, current) {
+                (Segment::Key(_), None | Some(serde_json::Value::Null)) => None,
+                (Segment::Key(key), Some(serde_json::Value::Object(object))) => object.get(key),
+                (Segment::Key(_), Some(_)) => {
+                    return Err(format!("{} is not an object", self.prefix(i)))
+                }
+                (Segment::Index(index), Some(serde_json::Value::Array(array))) => {
+                    match array.get(*index) {
+                        Some(next) => Some(next),
+                        None => {
+                            return Err(format!(
+                                "{} has {} entries, so there is no [{index}]",
+                                self.prefix(i),
+                                array.len()
+                            ))
+                        }
+                    }
+                }
+                (Segment::Index(_), _) => {
+                    return Err(format!("{} is not an array", self.prefix(i)))
+                }
+            };
+        }
+        Ok(())
+    }
+
+    /// The value at the path, if there is one
+    pub fn get<'a>(&self, root: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        self.segments
+            .iter()
+            .try_fold(root, |current, segment| match segment {
+                Segment::Key(key) => current.get(key),
+                Segment::Index(index) => current.get(index),
+            })
+    }
+
+    /// Remove the value at the path, returning it
+    ///
+    /// An object key is removed from its object, and an array entry from its array, so later
+    /// entries move up.
+    pub fn remove(&self, root: &mut serde_json::Value) -> Option<serde_json::Value> {
+        let (last, parents) = self.segments.split_last()?;
+        let parent = parents
+            .iter()
+            .try_fold(root, |current, segment| match segment {
+                Segment::Key(key) => current.get_mut(key),
+                Segment::Index(index) => current.get_mut(index),
+            })?;
+        match (last, parent) {
+            (Segment::Key(key), serde_json::Value::Object(object)) => object.remove(key),
+            (Segment::Index(index), serde_json::Value::Array(array)) if *index < array.len() => {
+                Some(array.remove(*index))
+            }
+            _ => None,
+        }
+    }
+
+    /// The path made up of the first `len` segments, for error messages
+    fn prefix(&self, len: usize) -> FieldPath {
+        FieldPath {
+            segments: self.segments[..len].to_vec(),
+        }
+    }
+}
+
+impl FromStr for FieldPath {
+    type Err = String;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = path;
+
+        loop {
+            // A key runs until the next `.` or `[`.
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let key = &rest[..end];
+            if key.is_empty() {
+                return Err(format!("{path:?} has an empty field name"));
+            }
+            if key.contains(']') {
+                return Err(format!("{path:?} has an unmatched ']'"));
+            }
+            segments.push(Segment::Key(key.to_string()));
+            rest = &rest[end..];
+
+            // Followed by any number of indexes.
+            while let Some(after) = rest.strip_prefix('[') {
+                let Some((index, after)) = after.split_once(']') else {
+                    return Err(format!("{path:?} has an unclosed '['"));
+                };
+                let Ok(index) = index.parse() else {
+                    return Err(format!("{path:?} has an invalid index [{index}]"));
+                };
+                segments.push(Segment::Index(index));
+                rest = after;
+            }
+
+            if rest.is_empty() {
+                return Ok(Self { segments });
+            }
+            match rest.strip_prefix('.') {
+                Some(after) => rest = after,
+                None => return Err(format!("{path:?} is missing a '.' after an index")),
+            }
+        }
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if i == 0 => write!(f, "{key}")?,
+                Segment::Key(key) => write!(f, ".{key}")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldPath, Segment};
+    use serde_json::json;
+
+    fn path(path: &str) -> FieldPath {
+        path.parse().unwrap()
+    }
+
+    #[test]
+    fn nested_paths_parse_into_segments() {
+        assert_eq!(
+            path("Rooms[0].Features[2]").segments(),
+            [
+                Segment::Key("Rooms".to_string()),
+                Segment::Index(0),
+                Segment::Key("Features".to_string()),
+                Segment::Index(2),
+            ]
+        );
+        assert_eq!(
+            path("Media[3].ImageWidth").to_string(),
+            "Media[3].ImageWidth"
+        );
+        assert_eq!(path("Matrix[1][2]").segments().len(), 3);
+        assert_eq!(path("ListPrice").segments().len(), 1);
+    }
+
+    #[test]
+    fn malformed_paths_are_rejected() {
+        let error = |path: &str| path.parse::<FieldPath>().unwrap_err();
+        assert_eq!(error(""), r#""" has an empty field name"#);
+        assert_eq!(
+            error("Rooms..Level"),
+            r#""Rooms..Level" has an empty field name"#
+        );
+        assert_eq!(error("Rooms."), r#""Rooms." has an empty field name"#);
+        assert_eq!(error("[0]"), r#""[0]" has an empty field name"#);
+        assert_eq!(error("Rooms[0"), r#""Rooms[0" has an unclosed '['"#);
+        assert_eq!(error("Rooms0]"), r#""Rooms0]" has an unmatched ']'"#);
+        assert_eq!(
+            error("Rooms[-1]"),
+            r#""Rooms[-1]" has an invalid index [-1]"#
+        );
+        assert_eq!(error("Rooms[x]"), r#""Rooms[x]" has an invalid index [x]"#);
+        assert_eq!(
+            error("Rooms[0]Level"),
+            r#""Rooms[0]Level" is missing a '.' after an index"#
+        );
+    }
+
+    #[test]
+    fn nested_values_are_looked_up() {
+        let data = json!({
+            "Rooms": [{ "RoomLevel": "Main", "Features": ["Fireplace", "Bay Window"] }],
+        });
+        assert_eq!(path("Rooms[0].RoomLevel").get(&data), Some(&json!("Main")));
+        assert_eq!(
+            path("Rooms[0].Features[1]").get(&data),
+            Some(&json!("Bay Window"))
+        );
+        assert_eq!(path("Rooms[1].RoomLevel").get(&data), None);
+        assert_eq!(path("Rooms[0].RoomType").get(&data), None);
+        assert_eq!(path("Rooms.RoomLevel").get(&data), None);
+    }
+
+    #[test]
+    fn checking_allows_missing_last_keys_but_not_out_of_range_indexes() {
+        let data = json!({ "Rooms": [{ "RoomLevel": "Main" }], "ListPrice": 1 });
+        assert_eq!(path("Rooms[0].RoomLevel").check(&data), Ok(()));
+        assert_eq!(path("Rooms[0].RoomType").check(&data), Ok(()));
+        assert_eq!(path("ClosePrice").check(&data), Ok(()));
+        assert_eq!(
+            path("Rooms[1].RoomLevel").check(&data),
+            Err("Rooms has 1 entries, so there is no [1]".to_string())
+        );
+        assert_eq!(
+            path("Media[0].ImageWidth").check(&data),
+            Err("Media does not exist".to_string())
+        );
+        assert_eq!(
+            path("ListPrice[0]").check(&data),
+            Err("ListPrice is not an array".to_string())
+        );
+        assert_eq!(
+            path("ListPrice.Amount").check(&data),
+            Err("ListPrice is not an object".to_string())
+        );
+    }
+
+    #[test]
+    fn setting_creates_missing_intermediate_objects() {
+        let mut data = json!({ "ListPrice": 1 });
+        path("Address.Unit.Number")
+            .set(&mut data, json!("4B"))
+            .unwrap();
+        assert_eq!(
+            data,
+            json!({ "ListPrice": 1, "Address": { "Unit": { "Number": "4B" } } })
+        );
+
+        let mut data = json!({ "Rooms": [{ "RoomLevel": null }] });
+        path("Rooms[0].RoomLevel.Name")
+            .set(&mut data, json!("Main"))
+            .unwrap();
+        assert_eq!(
+            data,
+            json!({ "Rooms": [{ "RoomLevel": { "Name": "Main" } }] })
+        );
+    }
+
+    #[test]
+    fn setting_never_extends_arrays_or_replaces_values() {
+        let mut data = json!({ "Rooms": [{ "RoomLevel": "Main" }], "ListPrice": 1 });
+        let before = data.clone();
+        assert_eq!(
+            path("Rooms[1].RoomLevel").set(&mut data, json!("Upper")),
+            Err("Rooms has 1 entries, so there is no [1]".to_string())
+        );
+        assert_eq!(
+            path("Media[0]").set(&mut data, json!({})),
+            Err("Media is not an array".to_string())
+        );
+        assert_eq!(
+            path("ListPrice.Amount").set(&mut data, json!(2)),
+            Err("ListPrice is not an object".to_string())
+        );
+        assert_eq!(
+            path("Address.Unit[0].Number").set(&mut data, json!("4B")),
+            Err("Address.Unit is not an array".to_string())
+        );
+        assert_eq!(
+            path("Rooms[0].RoomLevel.Name").set(&mut data, json!("Main")),
+            Err("Rooms[0].RoomLevel is not an object".to_string())
+        );
+        // Nothing was created on the way to any of the failures.
+        assert_eq!(data, before);
+
+        path("Rooms[0].RoomLevel")
+            .set(&mut data, json!("Upper"))
+            .unwrap();
+        assert_eq!(data["Rooms"], json!([{ "RoomLevel": "Upper" }]));
+    }
+
+    #[test]
+    fn removing_takes_keys_and_array_entries() {
+        let mut data = json!({ "Rooms": [{ "RoomLevel": "Main" }, { "RoomLevel": "Upper" }] });
+        assert_eq!(
+            path("Rooms[0].RoomLevel").remove(&mut data),
+            Some(json!("Main"))
+        );
+        assert_eq!(path("Rooms[0]").remove(&mut data), Some(json!({})));
+        assert_eq!(path("Rooms[5]").remove(&mut data), None);
+        assert_eq!(path("Media[0]").remove(&mut data), None);
+        assert_eq!(data, json!({ "Rooms": [{ "RoomLevel": "Upper" }] }));
+    }
+}
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, collections::BTreeMap};
+
+/// Limits on how much a module can ask of the host, so that a buggy module calling
+/// `reso.diagnostic` or `reso.error` in a loop can't drown it
+///
+/// Going over the number of calls or the total bytes of text traps the module. A single message
+/// that's too long is truncated instead, with a host warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quotas {
+    /// The most times the module may call any one host function
+    pub calls_per_function: u64,
+    /// The most bytes of text the module may hand over in total: messages (after truncation),
+    /// field names, values, picklists, rule IDs, related fields, and the host warnings they cause
+    pub message_bytes: usize,
+    /// The longest a single message may be, in bytes
+    pub message_len: usize,
+}
+
+impl Default for Quotas {
+    fn default() -> Self {
+        Self {
+            calls_per_function: 100_000,
+            message_bytes: 4 * 1024 * 1024,
+            message_len: 4096,
+        }
+    }
+}
+
+/// How much of its quotas a module has used so far
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    calls: BTreeMap<&'static str, u64>,
+    message_bytes: usize,
+}
+
+impl Usage {
+    /// Count a call to a host function, failing if it's one too many
+    pub(crate) fn call(&mut self, quotas: &Quotas, function: &'static str) -> anyhow::Result<()> {
+        let calls = self.calls.entry(function).or_default();
+        *calls += 1;
+        if *calls > quotas.calls_per_function {
+            anyhow::bail!(
+                "quota exceeded: reso.{function} was called more than {} times",
+                quotas.calls_per_function
+            );
+        }
+        Ok(())
+    }
+
+    /// Count a message, truncating it to the longest allowed, and fail if there has been too much
+    /// text in total
+    ///
+    /// Returns the message along with whether it was truncated.
+    pub(crate) fn message<'a>(
+        &mut self,
+        quotas: &Quotas,
+        message: &'a str,
+    ) -> anyhow::Result<(Cow<'a, str>, bool)> {
+        let mut message = Cow::Borrowed(message);
+        let truncated = message.len() > quotas.message_len;
+        if truncated {
+            // Leave room for the ellipsis, so the truncated message is no longer than allowed. If
+            // even the ellipsis doesn't fit, the message is just cut short.
+            let ellipsis = if quotas.message_len >= '…'.len_utf8() {
+                "…"
+            } else {
+                ""
+            };
+            let mut end = quotas.message_len - ellipsis.len();
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message = Cow::Owned(format!("{}{ellipsis}", &message[..end]));
+        }
+
+        self.text(quotas, &message)?;
+        Ok((message, truncated))
+    }
+
+    /// Count any other text the module hands over, failing if there has been too much in total
+    pub(crate) fn text(&mut self, quotas: &Quotas, text: &str) -> anyhow::Result<()> {
+        self.message_bytes = self.message_bytes.saturating_add(text.len());
+        if self.message_bytes > quotas.message_bytes {
+            anyhow::bail!(
+                "quota exceeded: the module produced more than {} bytes of text",
+                quotas.message_bytes
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quotas() -> Quotas {
+        Quotas {
+            calls_per_function: 2,
+            message_bytes: 10,
+            message_len: 6,
+        }
+    }
+
+    #[test]
+    fn calls_are_limited_per_function() {
+        let quotas = quotas();
+        let mut usage = Usage::default();
+        usage.call(&quotas, "error").unwrap();
+        usage.call(&quotas, "error").unwrap();
+        usage.call(&quotas, "warn").unwrap();
+
+        let err = usage.call(&quotas, "error").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "quota exceeded: reso.error was called more than 2 times"
+        );
+        usage.call(&quotas, "warn").unwrap();
+    }
+
+    #[test]
+    fn text_is_limited_in_total() {
+        let quotas = quotas();
+        let mut usage = Usage::default();
+        usage.message(&quotas, "abcd").unwrap();
+        usage.text(&quotas, "efghij").unwrap();
+
+        let err = usage.text(&quotas, "k").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "quota exceeded: the module produced more than 10 bytes of text"
+        );
+    }
+
+    #[test]
+    fn short_messages_are_untouched() {
+        let mut usage = Usage::default();
+        let (message, truncated) = usage.message(&quotas(), "abcdef").unwrap();
+        assert_eq!(message, "abcdef");
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn long_messages_are_truncated_to_the_limit() {
+        let mut usage = Usage::default();
+        let (message, truncated) = usage.message(&quotas(), "abcdefg").unwrap();
+        assert_eq!(message, "abc…");
+        assert!(message.len() <= quotas().message_len);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn truncation_stops_at_a_char_boundary() {
+        let quotas = quotas();
+        // "é" is two bytes, so the three bytes before the ellipsis end in the middle of one
+        let (message, truncated) = Usage::default().message(&quotas, "éééé").unwrap();
+        assert_eq!(message, "é…");
+        assert!(truncated);
+
+        let (message, _) = Usage::default().message(&quotas, "🏠🏠").unwrap();
+        assert_eq!(message, "…");
+    }
+
+    #[test]
+    fn truncated_messages_count_their_truncated_length() {
+        let quotas = quotas();
+        let mut usage = Usage::default();
+        // Each truncated message is six bytes, so a second one goes over the ten allowed
+        usage.message(&quotas, "abcdefghijklmnop").unwrap();
+        assert!(usage.message(&quotas, "abcdefghijklmnop").is_err());
+    }
+
+    #[test]
+    fn limits_too_short_for_an_ellipsis_cut_without_one() {
+        for (message_len, expected) in [(0, ""), (1, "a"), (2, "ab"), (3, "…")] {
+            let quotas = Quotas {
+                message_len,
+                ..quotas()
+            };
+            let (message, truncated) = Usage::default().message(&quotas, "abcdefg").unwrap();
+            assert_eq!(message, expected);
+            assert!(message.len() <= message_len);
+            assert!(truncated);
+        }
+
+        // A two-byte character doesn't fit in one byte.
+        let quotas = Quotas {
+            message_len: 1,
+            ..quotas()
+        };
+        let (message, _) = Usage::default().message(&quotas, "éé").unwrap();
+        assert_eq!(message, "");
+    }
+}
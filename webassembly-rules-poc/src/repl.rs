@@ -0,0 +1,211 @@
+use crate::{
+    load_listings, quotas, read_catalog, read_module, read_overlays, read_previous_data,
+    read_related, read_schema, run_layers, Args, EXIT_SUCCESS,
+};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+};
+use webassembly_rules_poc::{
+    expanded_collections, messages::Catalog, outcome::Outcome, path::FieldPath, schema::Schema,
+    Context, Error, Validator,
+};
+
+/// What `help` prints
+const HELP: &str = "\
+Commands:
+  set FIELD VALUE  Set a field and re-run. VALUE is JSON if it parses as JSON (250000, true,
+                   [\"Pool\"], \"42\"), and a string otherwise. FIELD can be nested, as in
+                   Rooms[0].RoomLevel.
+  unset FIELD      Remove a field and re-run
+  show [FIELD]     Show the listing, or a single field
+  outcome          Show everything the module said about the listing
+  help             Show this help
+  quit             Leave (so does Ctrl-D)";
+
+/// Load the listing, then edit it interactively, re-running the module after every change
+pub fn repl(args: &Args) -> Result<u8, Error> {
+    let webassembly = args.webassembly.as_deref().expect("required by clap");
+    let mut listings = load_listings(args)?;
+    if listings.len() != 1 {
+        return Err(Error::Usage(
+            "--interactive can only be used with a single listing".to_string(),
+        ));
+    }
+    let (_, mut data) = listings.remove(0);
+
+    let contents = read_module(args, webassembly)?;
+    let schema = read_schema(args)?;
+    let session = Session {
+        args,
+        module: webassembly.to_string_lossy().into_owned(),
+        validator: Validator::from_contents(args.runtime, webassembly, &contents, false)?,
+        overlays: read_overlays(args)?,
+        previous_data: match &args.previous_data {
+            Some(path) => {
+                serde_json::to_string(&read_previous_data(path, schema.as_ref())?).unwrap()
+            }
+            None => "null".to_string(),
+        },
+        related: read_related(args, schema.as_ref())?,
+        catalog: read_catalog(args)?,
+        schema,
+    };
+
+    let mut outcome = session.run(&data)?.unwrap_or_default();
+    outcome.print_human();
+    println!("Type `help` for a list of commands");
+
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("reso> ");
+        io::stdout().flush().unwrap();
+        let Some(Ok(line)) = lines.next() else {
+            println!();
+            break;
+        };
+
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let edited = match command {
+            "" => continue,
+            "set" => set(&mut data, rest),
+            "unset" => unset(&mut data, rest),
+            "show" => {
+                show(&data, rest);
+                continue;
+            }
+            "outcome" => {
+                outcome.print_human();
+                continue;
+            }
+            "help" => {
+                println!("{HELP}");
+                continue;
+            }
+            "quit" | "exit" => break,
+            _ => Err(format!(
+                "unknown command {command:?}; type `help` for a list"
+            )),
+        };
+        if let Err(problem) = edited {
+            println!("🚫 {problem}");
+            continue;
+        }
+
+        // A run that doesn't finish leaves the last good outcome in place to compare the next one
+        // with.
+        let Some(next) = session.run(&data)? else {
+            continue;
+        };
+        let changes = next.changes_since(&outcome);
+        if changes.is_empty() {
+            println!("   (no change)");
+        }
+        for change in changes {
+            println!("{change}");
+        }
+        outcome = next;
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+/// Everything that stays the same from one run to the next
+struct Session<'a> {
+    args: &'a Args,
+    /// The module's path, which what it says is tagged with when there are overlays
+    module: String,
+    validator: Validator,
+    overlays: Vec<(String, Validator)>,
+    previous_data: String,
+    related: BTreeMap<String, String>,
+    catalog: Catalog,
+    schema: Option<Schema>,
+}
+
+impl Session<'_> {
+    /// Validate the listing as it stands, or print why that wasn't possible
+    ///
+    /// With `--check-schema`, fields of the wrong type are reported and the module isn't run.
+    fn run(&self, data: &Value) -> Result<Option<Outcome>, Error> {
+        let mismatches = match &self.schema {
+            Some(schema) => schema.check(data),
+            None => Vec::new(),
+        };
+        if !mismatches.is_empty() {
+            for mismatch in mismatches {
+                println!("🧱 {mismatch}");
+            }
+            return Ok(None);
+        }
+
+        let context = || {
+            let context = Context::new(
+                serde_json::to_string(data).unwrap(),
+                self.previous_data.clone(),
+                self.args.verbose,
+            )
+            .with_related(expanded_collections(data))
+            .with_related(self.related.clone())
+            .with_messages(self.catalog.clone())
+            .with_quotas(quotas(self.args));
+            match &self.args.edited {
+                Some(edited) => context.with_edited(edited.clone()),
+                None => context,
+            }
+        };
+
+        let finished = run_layers(
+            &self.module,
+            &self.validator,
+            &self.overlays,
+            context(),
+            context,
+        )?;
+        if let Some(trap) = finished.trap {
+            println!("💥 {}", Error::Trap(trap));
+            return Ok(None);
+        }
+        Ok(Some(finished.context.outcome))
+    }
+}
+
+/// `set FIELD VALUE`
+fn set(data: &mut Value, rest: &str) -> Result<(), String> {
+    let Some((field, value)) = rest.split_once(char::is_whitespace) else {
+        return Err("usage: set FIELD VALUE".to_string());
+    };
+    let value = value.trim();
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+
+    field.parse::<FieldPath>()?.set(data, value)
+}
+
+/// `unset FIELD`
+fn unset(data: &mut Value, field: &str) -> Result<(), String> {
+    if field.is_empty() {
+        return Err("usage: unset FIELD".to_string());
+    }
+    match field.parse::<FieldPath>()?.remove(data) {
+        Some(_) => Ok(()),
+        None => Err(format!("{field} is not set")),
+    }
+}
+
+/// `show [FIELD]`
+fn show(data: &Value, field: &str) {
+    if field.is_empty() {
+        println!("{}", serde_json::to_string_pretty(data).unwrap());
+        return;
+    }
+    match field
+        .parse::<FieldPath>()
+        .map(|path| path.get(data).cloned())
+    {
+        Ok(Some(value)) => println!("{}", serde_json::to_string_pretty(&value).unwrap()),
+        Ok(None) => println!("{field} is not set"),
+        Err(problem) => println!("🚫 {problem}"),
+    }
+}
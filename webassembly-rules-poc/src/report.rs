@@ -0,0 +1,531 @@
+use crate::{
+    metadata::Metadata,
+    outcome::{Message, Outcome},
+    schema::Mismatch,
+    stats::Stats,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::fmt::Write;
+
+/// The result of validating a single listing
+#[derive(Debug, Serialize)]
+pub struct ListingReport {
+    /// Where the listing came from, usually a file path
+    pub listing: String,
+    /// Everything the module said about the listing
+    pub outcome: Outcome,
+    /// Why `validate` trapped, if it did, with the runtime's backtrace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trap: Option<String>,
+    /// The root cause of the trap, such as an `unreachable` instruction, without the backtrace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trap_cause: Option<String>,
+    /// Statistics about the run, if they were collected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Stats>,
+    /// How a second runtime's outcome differed, in a differential run
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disagreements: Vec<String>,
+    /// Fields whose values don't have the types the schema gives them, when the data was
+    /// checked. The module isn't run on such a listing, so its outcome is empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub schema_mismatches: Vec<Mismatch>,
+}
+
+/// Render the reports as a single JSON document
+///
+/// Includes the module's metadata, if it has any, so a report says which rules produced it.
+pub fn to_json(module: &str, metadata: Option<&Metadata>, reports: &[ListingReport]) -> String {
+    let mut document = json!({
+        "module": module,
+        "listings": reports,
+    });
+    if let Some(metadata) = metadata {
+        document["metadata"] = json!(metadata);
+    }
+    serde_json::to_string_pretty(&document).unwrap()
+}
+
+/// Render the reports as JUnit XML
+///
+/// The module is the test suite and every listing is a test case. Each error becomes a
+/// `<failure>` whose type is the rule that produced it (or the field, if the module didn't say), a
+/// trap becomes an `<error>` with its root cause as the message and the backtrace as the body, and
+/// warnings are listed in `<system-out>`. A listing that didn't match the schema is an `<error>`
+/// listing the fields that didn't.
+pub fn to_junit(module: &str, reports: &[ListingReport]) -> String {
+    let errors = reports
+        .iter()
+        .filter(|report| report.trap.is_some() || !report.schema_mismatches.is_empty())
+        .count();
+    let failures = reports
+        .iter()
+        .filter(|report| report.trap.is_none() && report.outcome.has_errors())
+        .count();
+
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        xml,
+        r#"<testsuites name="{}" tests="{}" failures="{failures}" errors="{errors}">"#,
+        escape_xml(env!("CARGO_PKG_NAME")),
+        reports.len(),
+    )
+    .unwrap();
+    writeln!(
+        xml,
+        r#"  <testsuite name="{}" tests="{}" failures="{failures}" errors="{errors}">"#,
+        escape_xml(module),
+        reports.len(),
+    )
+    .unwrap();
+
+    for report in reports {
+        writeln!(
+            xml,
+            r#"    <testcase classname="{}" name="{}">"#,
+            escape_xml(module),
+            escape_xml(&report.listing),
+        )
+        .unwrap();
+
+        for (field, state) in &report.outcome.fields {
+            for error in &state.errors {
+                writeln!(
+                    xml,
+                    r#"      <failure type="{}" message="{}">{}</failure>"#,
+                    escape_xml(error.rule_id.as_deref().unwrap_or(field)),
+                    escape_xml(&error.message),
+                    escape_xml(&plain_text(field, error)),
+                )
+                .unwrap();
+            }
+        }
+        if !report.schema_mismatches.is_empty() {
+            writeln!(
+                xml,
+                r#"      <error type="schema" message="{}">{}</error>"#,
+                escape_xml(&schema_problem(&report.schema_mismatches)),
+                escape_xml(&lines(&report.schema_mismatches)),
+            )
+            .unwrap();
+        }
+        if let Some(trap) = &report.trap {
+            writeln!(
+                xml,
+                r#"      <error type="trap" message="{}">{}</error>"#,
+                escape_xml(report.trap_cause.as_deref().unwrap_or(trap)),
+                escape_xml(trap),
+            )
+            .unwrap();
+        }
+
+        let warnings = report
+            .outcome
+            .fields
+            .iter()
+            .flat_map(|(field, state)| {
+                state
+                    .warnings
+                    .iter()
+                    .map(move |warning| format!("warning: {}", plain_text(field, warning)))
+            })
+            .chain(
+                report
+                    .outcome
+                    .host_warnings
+                    .iter()
+                    .map(|warning| format!("host warning: {warning}")),
+            )
+            .collect::<Vec<_>>();
+        if !warnings.is_empty() {
+            writeln!(
+                xml,
+                "      <system-out>{}</system-out>",
+                escape_xml(&warnings.join("\n"))
+            )
+            .unwrap();
+        }
+
+        writeln!(xml, "    </testcase>").unwrap();
+    }
+
+    writeln!(xml, "  </testsuite>").unwrap();
+    writeln!(xml, "</testsuites>").unwrap();
+    xml
+}
+
+/// Render the reports as a SARIF 2.1.0 log
+///
+/// Every error and warning becomes a result, located in the listing's file and logically at the
+/// field it was reported against, with the rule that produced it as the result's `ruleId` and any
+/// related fields as related locations. The module that reported it, if modules were layered, is
+/// kept in the result's properties. Traps, and listings that didn't match the schema, become tool
+/// execution notifications.
+pub fn to_sarif(module: &str, reports: &[ListingReport]) -> String {
+    let mut results = Vec::new();
+    let mut notifications = Vec::new();
+
+    for report in reports {
+        for (field, state) in &report.outcome.fields {
+            let messages = state
+                .errors
+                .iter()
+                .map(|message| ("error", message))
+                .chain(state.warnings.iter().map(|message| ("warning", message)));
+            for (level, message) in messages {
+                let mut result = json!({
+                    "level": level,
+                    "message": { "text": format!("{field}: {}", message.message) },
+                    "locations": [location(&report.listing, field)],
+                });
+                if let Some(rule_id) = &message.rule_id {
+                    result["ruleId"] = json!(rule_id);
+                }
+                if !message.related_fields.is_empty() {
+                    result["relatedLocations"] = message
+                        .related_fields
+                        .iter()
+                        .map(|related| location(&report.listing, related))
+                        .collect();
+                }
+                if let Some(module) = &message.module {
+                    result["properties"] = json!({ "module": module });
+                }
+                results.push(result);
+            }
+        }
+
+        if !report.schema_mismatches.is_empty() {
+            let problem = schema_problem(&report.schema_mismatches);
+            notifications.push(json!({
+                "level": "error",
+                "message": {
+                    "text": format!("{problem}:\n{}", lines(&report.schema_mismatches)),
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": report.listing },
+                    },
+                }],
+            }));
+        }
+        if let Some(trap) = &report.trap {
+            notifications.push(json!({
+                "level": "error",
+                "message": { "text": trap },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": report.listing },
+                    },
+                }],
+            }));
+        }
+    }
+
+    let document = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            },
+            "artifacts": [{ "location": { "uri": module } }],
+            "invocations": [{
+                "executionSuccessful": notifications.is_empty(),
+                "toolExecutionNotifications": notifications,
+            }],
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&document).unwrap()
+}
+
+/// A SARIF location pointing at a field in a listing
+fn location(listing: &str, field: &str) -> serde_json::Value {
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": listing },
+        },
+        "logicalLocations": [{ "name": field, "kind": "member" }],
+    })
+}
+
+/// Describe a message on a single line without any terminal colors
+fn plain_text(field: &str, message: &Message) -> String {
+    let mut text = format!("{field}: {}", message.message);
+    if !message.related_fields.is_empty() {
+        text.push_str(&format!(" (with {})", message.related_fields.join(", ")));
+    }
+    if let Some(rule_id) = &message.rule_id {
+        text.push_str(&format!(" [{rule_id}]"));
+    }
+    if let Some(module) = &message.module {
+        text.push_str(&format!(" from {module}"));
+    }
+    text
+}
+
+/// Summarize why a listing wasn't validated because it didn't match the schema
+fn schema_problem(mismatches: &[Mismatch]) -> String {
+    match mismatches.len() {
+        1 => "1 field doesn't have the RESO Data Dictionary's type".to_string(),
+        count => format!("{count} fields don't have the RESO Data Dictionary's types"),
+    }
+}
+
+/// Put every item on a line of its own
+fn lines(items: &[impl std::fmt::Display]) -> String {
+    let lines: Vec<_> = items.iter().map(ToString::to_string).collect();
+    lines.join("\n")
+}
+
+/// Escape a string for use in XML text or attribute values
+///
+/// Characters XML 1.0 doesn't allow at all, even escaped, such as most control characters, become
+/// U+FFFD REPLACEMENT CHARACTER, so a module's messages can't make the document unparseable.
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {
+                escaped.push(char::REPLACEMENT_CHARACTER)
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_junit, to_sarif, ListingReport};
+    use crate::{
+        outcome::{Message, Outcome, Severity},
+        schema::{FieldType, Mismatch},
+        Context, Runtime, Validator,
+    };
+    use serde_json::json;
+
+    fn report(listing: &str, outcome: Outcome) -> ListingReport {
+        ListingReport {
+            listing: listing.to_string(),
+            outcome,
+            trap: None,
+            trap_cause: None,
+            stats: None,
+            disagreements: Vec::new(),
+            schema_mismatches: Vec::new(),
+        }
+    }
+
+    /// A failing listing, a trapped one, one that didn't match the schema, and one that passed,
+    /// with control characters from the module in its messages
+    fn reports() -> Vec<ListingReport> {
+        let mut failed = Outcome::default();
+        failed.report(
+            "ListPrice",
+            Severity::Error,
+            Message {
+                message: "too\u{0} low\u{8}\u{b}\u{c}\u{1f} <&\"'>".to_string(),
+                rule_id: Some("LP-1".to_string()),
+                related_fields: vec!["OriginalListPrice".to_string()],
+                module: None,
+            },
+        );
+        failed.warn("City", "check\u{1b}[31m spelling");
+        failed
+            .host_warnings
+            .push("ListPrice: value was set to 1 twice".to_string());
+
+        let mut trapped = report("trapped.json", Outcome::default());
+        trapped.trap = Some("wasm trap: unreachable\n\nwasm backtrace:\n  0: validate".into());
+        trapped.trap_cause = Some("wasm trap: unreachable".to_string());
+
+        let mut mismatched = report("mismatched.json", Outcome::default());
+        mismatched.schema_mismatches = vec![Mismatch {
+            path: "ListPrice".to_string(),
+            expected: FieldType::Number,
+            found: json!("cheap\u{7}"),
+        }];
+
+        vec![
+            report("failed.json", failed),
+            trapped,
+            mismatched,
+            report("passed.json", Outcome::default()),
+        ]
+    }
+
+    #[test]
+    fn junit_parses_even_with_control_characters() {
+        let xml = to_junit("rules.wasm", &reports());
+        let document = roxmltree::Document::parse(&xml).unwrap();
+
+        let suite = document
+            .descendants()
+            .find(|node| node.has_tag_name("testsuite"))
+            .unwrap();
+        assert_eq!(suite.attribute("name"), Some("rules.wasm"));
+        assert_eq!(suite.attribute("tests"), Some("4"));
+        assert_eq!(suite.attribute("failures"), Some("1"));
+        assert_eq!(suite.attribute("errors"), Some("2"));
+
+        let cases: Vec<_> = suite
+            .children()
+            .filter(|node| node.has_tag_name("testcase"))
+            .collect();
+        assert_eq!(cases.len(), 4);
+
+        let failure = cases[0]
+            .children()
+            .find(|node| node.has_tag_name("failure"))
+            .unwrap();
+        assert_eq!(failure.attribute("type"), Some("LP-1"));
+        assert_eq!(
+            failure.attribute("message"),
+            Some("too\u{fffd} low\u{fffd}\u{fffd}\u{fffd}\u{fffd} <&\"'>")
+        );
+        let out = cases[0]
+            .children()
+            .find(|node| node.has_tag_name("system-out"))
+            .unwrap();
+        assert_eq!(
+            out.text(),
+            Some(
+                "warning: City: check\u{fffd}[31m spelling\n\
+                 host warning: ListPrice: value was set to 1 twice"
+            )
+        );
+
+        let errors: Vec<_> = cases[1..3]
+            .iter()
+            .map(|case| {
+                let error = case
+                    .children()
+                    .find(|node| node.has_tag_name("error"))
+                    .unwrap();
+                (error.attribute("type"), error.attribute("message"))
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (Some("trap"), Some("wasm trap: unreachable")),
+                (
+                    Some("schema"),
+                    Some("1 field doesn't have the RESO Data Dictionary's type")
+                ),
+            ]
+        );
+        assert!(cases[3].children().all(|node| !node.is_element()));
+    }
+
+    #[test]
+    fn sarif_has_a_result_per_message_and_a_notification_per_problem() {
+        let sarif: serde_json::Value =
+            serde_json::from_str(&to_sarif("rules.wasm", &reports())).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["artifacts"][0]["location"]["uri"], "rules.wasm");
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["level"], "warning");
+        assert_eq!(
+            results[0]["message"]["text"],
+            "City: check\u{1b}[31m spelling"
+        );
+        assert_eq!(results[1]["level"], "error");
+        assert_eq!(results[1]["ruleId"], "LP-1");
+        assert_eq!(
+            results[1]["message"]["text"],
+            "ListPrice: too\u{0} low\u{8}\u{b}\u{c}\u{1f} <&\"'>"
+        );
+        let location = &results[1]["locations"][0];
+        assert_eq!(
+            location["physicalLocation"]["artifactLocation"]["uri"],
+            "failed.json"
+        );
+        assert_eq!(location["logicalLocations"][0]["name"], "ListPrice");
+        assert_eq!(
+            results[1]["relatedLocations"][0]["logicalLocations"][0]["name"],
+            "OriginalListPrice"
+        );
+
+        let invocation = &run["invocations"][0];
+        assert_eq!(invocation["executionSuccessful"], false);
+        let notifications: Vec<_> = invocation["toolExecutionNotifications"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|notification| {
+                (
+                    notification["locations"][0]["physicalLocation"]["artifactLocation"]["uri"]
+                        .as_str()
+                        .unwrap(),
+                    notification["message"]["text"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            notifications,
+            [
+                (
+                    "trapped.json",
+                    "wasm trap: unreachable\n\nwasm backtrace:\n  0: validate"
+                ),
+                (
+                    "mismatched.json",
+                    "1 field doesn't have the RESO Data Dictionary's type:\n\
+                     ListPrice: expected a number, found \"cheap\\u0007\""
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn junit_traps_are_described_by_their_root_cause() {
+        let module = wat::parse_str(
+            r#"(module (memory (export "memory") 1) (func (export "validate") unreachable))"#,
+        )
+        .unwrap();
+        let validator = Validator::from_bytes(Runtime::Wasmtime, &module, false).unwrap();
+        let context = Context::new("{}".to_string(), "null".to_string(), 0);
+        let trap = validator.run(context).unwrap().trap.unwrap();
+
+        let report = ListingReport {
+            listing: "listing.json".to_string(),
+            outcome: Default::default(),
+            trap: Some(format!("{trap:#}")),
+            trap_cause: Some(trap.root_cause().to_string()),
+            stats: None,
+            disagreements: Vec::new(),
+            schema_mismatches: Vec::new(),
+        };
+        let xml = to_junit("rules.wasm", &[report]);
+
+        let error = xml
+            .lines()
+            .find(|line| line.contains("<error "))
+            .unwrap()
+            .trim();
+        assert!(
+            error.starts_with(
+                r#"<error type="trap" message="wasm trap: wasm `unreachable` instruction executed">"#
+            ),
+            "{error}"
+        );
+        assert!(xml.contains("wasm backtrace:"), "{xml}");
+    }
+}
@@ -0,0 +1,476 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, path::Path};
+
+/// The resource a listing is an instance of
+const PROPERTY: &str = "Property";
+
+/// The type of a field, as the RESO Data Dictionary describes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldType {
+    /// Free text, or a single lookup value
+    String,
+    Boolean,
+    /// Any number, such as a price or an area
+    Number,
+    /// A whole number, such as a count of rooms
+    Integer,
+    /// A date, as in `2023-08-14`
+    Date,
+    /// A date and time with an offset, as in `2023-08-14T09:30:00Z`
+    Timestamp,
+    /// A multiple-value lookup, given as an array of strings
+    StringList,
+}
+
+impl FieldType {
+    /// Whether a value is acceptable for a field of this type. Every field may be null.
+    fn accepts(self, value: &Value) -> bool {
+        match (self, value) {
+            (_, Value::Null) => true,
+            (FieldType::String, Value::String(_)) => true,
+            (FieldType::Boolean, Value::Bool(_)) => true,
+            (FieldType::Number, Value::Number(_)) => true,
+            (FieldType::Integer, Value::Number(number)) => number.is_i64() || number.is_u64(),
+            (FieldType::Date, Value::String(date)) => is_date(date),
+            (FieldType::Timestamp, Value::String(timestamp)) => is_timestamp(timestamp),
+            (FieldType::StringList, Value::Array(values)) => values.iter().all(Value::is_string),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldType::String => write!(f, "a string"),
+            FieldType::Boolean => write!(f, "a boolean"),
+            FieldType::Number => write!(f, "a number"),
+            FieldType::Integer => write!(f, "a whole number"),
+            FieldType::Date => write!(f, "a date (YYYY-MM-DD)"),
+            FieldType::Timestamp => write!(f, "a timestamp (YYYY-MM-DDTHH:MM:SSZ)"),
+            FieldType::StringList => write!(f, "an array of strings"),
+        }
+    }
+}
+
+/// What a field in a resource holds: a value, or an expanded collection of another resource
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Field {
+    Value(FieldType),
+    /// A collection expanded inline, such as `Rooms`, holding the resource with the given name
+    Collection {
+        collection: String,
+    },
+}
+
+/// A field whose value doesn't have the type the schema gives it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Mismatch {
+    /// Where the field is, as in `ListPrice` or `Rooms[0].RoomArea`
+    pub path: String,
+    pub expected: FieldType,
+    /// The value the field actually had
+    pub found: Value,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, found {}",
+            self.path, self.expected, self.found
+        )
+    }
+}
+
+/// The types of the fields in each resource
+///
+/// Fields the schema doesn't know about are never mismatches, since MLSs are free to add local
+/// fields.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    resources: BTreeMap<String, BTreeMap<String, Field>>,
+}
+
+impl Schema {
+    /// The types of the most commonly used fields in the RESO Data Dictionary
+    pub fn data_dictionary() -> Self {
+        use FieldType::*;
+
+        let property = [
+            ("ListingKey", String),
+            ("ListingId", String),
+            ("StandardStatus", String),
+            ("MlsStatus", String),
+            ("PropertyType", String),
+            ("PropertySubType", String),
+            ("ListPrice", Number),
+            ("OriginalListPrice", Number),
+            ("PreviousListPrice", Number),
+            ("ClosePrice", Number),
+            ("ListingContractDate", Date),
+            ("OnMarketDate", Date),
+            ("PurchaseContractDate", Date),
+            ("CloseDate", Date),
+            ("ExpirationDate", Date),
+            ("ModificationTimestamp", Timestamp),
+            ("OriginalEntryTimestamp", Timestamp),
+            ("StatusChangeTimestamp", Timestamp),
+            ("PriceChangeTimestamp", Timestamp),
+            ("DaysOnMarket", Integer),
+            ("CumulativeDaysOnMarket", Integer),
+            ("BedroomsTotal", Integer),
+            ("BathroomsTotalInteger", Integer),
+            ("BathroomsFull", Integer),
+            ("BathroomsHalf", Integer),
+            ("RoomsTotal", Integer),
+            ("StoriesTotal", Integer),
+            ("YearBuilt", Integer),
+            ("GarageSpaces", Number),
+            ("ParkingTotal", Number),
+            ("LivingArea", Number),
+            ("BuildingAreaTotal", Number),
+            ("LotSizeArea", Number),
+            ("LotSizeAcres", Number),
+            ("LotSizeSquareFeet", Number),
+            ("Latitude", Number),
+            ("Longitude", Number),
+            ("TaxAnnualAmount", Number),
+            ("TaxYear", Integer),
+            ("AssociationFee", Number),
+            ("AssociationYN", Boolean),
+            ("PoolPrivateYN", Boolean),
+            ("WaterfrontYN", Boolean),
+            ("NewConstructionYN", Boolean),
+            ("InternetEntireListingDisplayYN", Boolean),
+            ("InternetAddressDisplayYN", Boolean),
+            ("StreetNumber", String),
+            ("StreetName", String),
+            ("UnparsedAddress", String),
+            ("City", String),
+            ("StateOrProvince", String),
+            ("PostalCode", String),
+            ("Country", String),
+            ("PublicRemarks", String),
+            ("PrivateRemarks", String),
+            ("ListAgentKey", String),
+            ("ListOfficeKey", String),
+            ("Appliances", StringList),
+            ("Cooling", StringList),
+            ("Heating", StringList),
+            ("InteriorFeatures", StringList),
+            ("ExteriorFeatures", StringList),
+            ("Flooring", StringList),
+            ("View", StringList),
+        ];
+        let rooms = [
+            ("RoomKey", String),
+            ("RoomType", String),
+            ("RoomLevel", String),
+            ("RoomArea", Number),
+            ("RoomLength", Number),
+            ("RoomWidth", Number),
+            ("RoomDescription", String),
+            ("RoomFeatures", StringList),
+        ];
+        let media = [
+            ("MediaKey", String),
+            ("MediaURL", String),
+            ("MediaCategory", String),
+            ("MimeType", String),
+            ("Order", Integer),
+            ("ImageWidth", Integer),
+            ("ImageHeight", Integer),
+            ("ShortDescription", String),
+            ("ModificationTimestamp", Timestamp),
+        ];
+        let unit_types = [
+            ("UnitTypeKey", String),
+            ("UnitTypeType", String),
+            ("UnitTypeBedsTotal", Integer),
+            ("UnitTypeBathsTotal", Integer),
+            ("UnitTypeActualRent", Number),
+            ("UnitTypeUnitsTotal", Integer),
+        ];
+
+        let fields = |fields: &[(&str, FieldType)]| {
+            fields
+                .iter()
+                .map(|(name, field_type)| (name.to_string(), Field::Value(*field_type)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let mut property = fields(&property);
+        for (name, resource) in [
+            ("Rooms", "PropertyRooms"),
+            ("Media", "Media"),
+            ("UnitTypes", "PropertyUnitTypes"),
+        ] {
+            let collection = resource.to_string();
+            property.insert(name.to_string(), Field::Collection { collection });
+        }
+
+        Self {
+            resources: BTreeMap::from([
+                (PROPERTY.to_string(), property),
+                ("PropertyRooms".to_string(), fields(&rooms)),
+                ("Media".to_string(), fields(&media)),
+                ("PropertyUnitTypes".to_string(), fields(&unit_types)),
+            ]),
+        }
+    }
+
+    /// Add the field types in a file to the schema, replacing any it already has
+    ///
+    /// The file maps resources to fields to types, as in
+    /// `{"Property": {"ListPriceLow": "Number", "Showings": {"collection": "Showing"}}}`.
+    pub fn extend_from_file(&mut self, path: &Path) -> Result<(), Error> {
+        let contents = std::fs::read(path).map_err(|source| Error::ReadInput {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let resources: BTreeMap<String, BTreeMap<String, Field>> =
+            serde_json::from_slice(&contents).map_err(|source| Error::ParseInput {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        for (resource, fields) in resources {
+            self.resources.entry(resource).or_default().extend(fields);
+        }
+        Ok(())
+    }
+
+    /// Find every field of a listing whose value doesn't have the type the schema gives it
+    pub fn check(&self, listing: &Value) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        self.check_resource(PROPERTY, listing, "", &mut mismatches);
+        mismatches
+    }
+
+    /// Find every field of a related resource, such as `--related Media=media.json`, whose value
+    /// doesn't have the type the schema gives it
+    ///
+    /// The resource is checked as the listing's expanded collection of the same name, so
+    /// mismatches are named like `Media[0].MediaURL`. A resource that isn't one of the listing's
+    /// collections, such as `OpenHouse`, has no types to check.
+    pub fn check_related(&self, name: &str, resource: &Value) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        if let Some(Field::Collection { collection }) = self
+            .resources
+            .get(PROPERTY)
+            .and_then(|fields| fields.get(name))
+        {
+            self.check_collection(collection, resource, name, &mut mismatches);
+        }
+        mismatches
+    }
+
+    fn check_resource(
+        &self,
+        resource: &str,
+        value: &Value,
+        prefix: &str,
+        mismatches: &mut Vec<Mismatch>,
+    ) {
+        let (Some(fields), Some(object)) = (self.resources.get(resource), value.as_object()) else {
+            return;
+        };
+        for (name, value) in object {
+            let path = format!("{prefix}{name}");
+            match fields.get(name) {
+                Some(Field::Value(expected)) if !expected.accepts(value) => {
+                    mismatches.push(Mismatch {
+                        path,
+                        expected: *expected,
+                        found: value.clone(),
+                    })
+                }
+                Some(Field::Collection { collection }) => {
+                    self.check_collection(collection, value, &path, mismatches)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_collection(
+        &self,
+        collection: &str,
+        value: &Value,
+        path: &str,
+        mismatches: &mut Vec<Mismatch>,
+    ) {
+        for (i, item) in value.as_array().into_iter().flatten().enumerate() {
+            let prefix = format!("{path}[{i}].");
+            self.check_resource(collection, item, &prefix, mismatches);
+        }
+    }
+}
+
+/// Whether a string looks like `YYYY-MM-DD`
+fn is_date(date: &str) -> bool {
+    let bytes = date.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, byte)| match i {
+            4 | 7 => *byte == b'-',
+            _ => byte.is_ascii_digit(),
+        })
+}
+
+/// Whether a string looks like an RFC 3339 timestamp, as in `2023-08-14T09:30:00.000Z` or
+/// `2023-08-14T09:30:00-05:00`
+fn is_timestamp(timestamp: &str) -> bool {
+    let Some((date, time)) = timestamp.split_once('T') else {
+        return false;
+    };
+    // Slicing at a byte offset that isn't a character boundary would panic, so use `get`.
+    let (Some(clock), Some(mut rest)) = (time.get(..8), time.get(8..)) else {
+        return false;
+    };
+    if !is_date(date) || !is_clock(clock) {
+        return false;
+    }
+
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return false;
+        }
+        rest = &fraction[digits..];
+    }
+    match rest.strip_prefix(['+', '-']) {
+        Some(offset) => offset.len() == 5 && is_clock(&format!("{offset}:00")),
+        None => rest == "Z",
+    }
+}
+
+/// Whether a string looks like `HH:MM:SS`
+fn is_clock(clock: &str) -> bool {
+    let bytes = clock.as_bytes();
+    bytes.len() == 8
+        && bytes.iter().enumerate().all(|(i, byte)| match i {
+            2 | 5 => *byte == b':',
+            _ => byte.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_clock, is_date, is_timestamp, FieldType, Mismatch, Schema};
+    use serde_json::json;
+
+    #[test]
+    fn dates() {
+        assert!(is_date("2023-08-14"));
+        assert!(!is_date("2023-8-14"));
+        assert!(!is_date("2023-08-1"));
+        assert!(!is_date("2023/08/14"));
+        assert!(!is_date("2023-08-14T"));
+        assert!(!is_date("2023-08-1é"));
+        assert!(!is_date("２023-08-14"));
+        assert!(!is_date(""));
+    }
+
+    #[test]
+    fn clocks() {
+        assert!(is_clock("09:30:00"));
+        assert!(!is_clock("9:30:00"));
+        assert!(!is_clock("09:30"));
+        assert!(!is_clock("09-30-00"));
+        assert!(!is_clock("09:30:0é"));
+        assert!(!is_clock(""));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert!(is_timestamp("2023-08-14T09:30:00Z"));
+        assert!(is_timestamp("2023-08-14T09:30:00.000Z"));
+        assert!(is_timestamp("2023-08-14T09:30:00-05:00"));
+        assert!(is_timestamp("2023-08-14T09:30:00.5+01:00"));
+        assert!(!is_timestamp("2023-08-14"));
+        assert!(!is_timestamp("2023-08-14T09:30Z"));
+        assert!(!is_timestamp("2023-08-14T09:30:00"));
+        assert!(!is_timestamp("2023-08-14T09:30:00.Z"));
+        assert!(!is_timestamp("2023-08-14T09:30:00+5:00"));
+        assert!(!is_timestamp("2023-08-14T"));
+    }
+
+    #[test]
+    fn timestamps_with_non_ascii_characters_are_rejected_without_panicking() {
+        assert!(!is_timestamp("2023-08-14T09:30:0é"));
+        assert!(!is_timestamp("2023-08-14T09:30:0éZ"));
+        assert!(!is_timestamp("2023-08-14Té"));
+        assert!(!is_timestamp("2023-08-14T09:30:00.éZ"));
+        assert!(!is_timestamp("2023-08-14T09:30:00+05:0é"));
+        assert!(!is_timestamp("2023-08-14T09:30:00Zé"));
+        assert!(!is_timestamp("é023-08-14T09:30:00Z"));
+    }
+
+    #[test]
+    fn matching_listings_have_no_mismatches() {
+        let listing = json!({
+            "ListPrice": 250000.5,
+            "BedroomsTotal": 3,
+            "CloseDate": "2023-08-14",
+            "ModificationTimestamp": "2023-08-14T09:30:00Z",
+            "PoolPrivateYN": false,
+            "Appliances": ["Dishwasher"],
+            "ClosePrice": null,
+            "LocalField": { "anything": true },
+            "Rooms": [{ "RoomArea": 120 }],
+        });
+        assert_eq!(Schema::data_dictionary().check(&listing), []);
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_are_mismatches() {
+        let listing = json!({
+            "ListPrice": "250000",
+            "BedroomsTotal": 2.5,
+            "CloseDate": "August 14",
+            "ModificationTimestamp": "2023-08-14T09:30:0é",
+            "PoolPrivateYN": "Y",
+            "Appliances": ["Dishwasher", 1],
+            "Rooms": [{ "RoomArea": 120 }, { "RoomArea": "big" }],
+        });
+        let mismatches = Schema::data_dictionary().check(&listing);
+        let paths: Vec<_> = mismatches
+            .iter()
+            .map(|mismatch| mismatch.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "Appliances",
+                "BedroomsTotal",
+                "CloseDate",
+                "ListPrice",
+                "ModificationTimestamp",
+                "PoolPrivateYN",
+                "Rooms[1].RoomArea",
+            ]
+        );
+        assert_eq!(
+            mismatches[3],
+            Mismatch {
+                path: "ListPrice".to_string(),
+                expected: FieldType::Number,
+                found: json!("250000"),
+            }
+        );
+        assert_eq!(
+            mismatches[3].to_string(),
+            r#"ListPrice: expected a number, found "250000""#
+        );
+    }
+
+    #[test]
+    fn only_objects_are_checked() {
+        let schema = Schema::data_dictionary();
+        assert_eq!(schema.check(&json!(null)), []);
+        assert_eq!(schema.check(&json!(["ListPrice"])), []);
+        assert_eq!(schema.check(&json!({ "Rooms": "none" })), []);
+    }
+}
@@ -0,0 +1,125 @@
+use std::ops::Range;
+
+/// The magic number and version every WebAssembly binary starts with
+const HEADER: &[u8] = b"\0asm\x01\0\0\0";
+
+/// The id of a custom section
+const CUSTOM: u8 = 0;
+
+/// A single section of a WebAssembly binary
+#[derive(Debug, Clone)]
+pub struct Section<'a> {
+    /// The section's id. Custom sections are 0.
+    pub id: u8,
+    /// Where the whole section, including its id and size, sits in the module
+    pub range: Range<usize>,
+    /// The section's contents
+    pub payload: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+    /// The name and contents of a custom section, or `None` for any other kind of section
+    pub fn custom(&self) -> Option<(&'a str, &'a [u8])> {
+        if self.id != CUSTOM {
+            return None;
+        }
+        let mut offset = 0;
+        let len = read_u32(self.payload, &mut offset).ok()? as usize;
+        let name = self.payload.get(offset..offset + len)?;
+        let name = std::str::from_utf8(name).ok()?;
+        Some((name, &self.payload[offset + len..]))
+    }
+}
+
+/// Split a WebAssembly binary into its sections
+///
+/// Only the section framing is checked; the sections themselves are left for the runtime.
+pub fn sections(module: &[u8]) -> Result<Vec<Section<'_>>, String> {
+    if !module.starts_with(HEADER) {
+        return Err("not a WebAssembly binary".to_string());
+    }
+
+    let mut sections = Vec::new();
+    let mut offset = HEADER.len();
+    while offset < module.len() {
+        let start = offset;
+        let id = module[offset];
+        offset += 1;
+        let len = read_u32(module, &mut offset)? as usize;
+        let Some(payload) = module.get(offset..offset + len) else {
+            return Err(format!(
+                "section at offset {start} runs past the end of the module"
+            ));
+        };
+        offset += len;
+        sections.push(Section {
+            id,
+            range: start..offset,
+            payload,
+        });
+    }
+    Ok(sections)
+}
+
+/// Find the contents of the first custom section with the given name
+pub fn find_custom<'a>(module: &'a [u8], name: &str) -> Result<Option<&'a [u8]>, String> {
+    Ok(sections(module)?
+        .iter()
+        .filter_map(Section::custom)
+        .find(|(section, _)| *section == name)
+        .map(|(_, contents)| contents))
+}
+
+/// The module with every custom section of the given name removed
+pub fn without_custom(module: &[u8], name: &str) -> Result<Vec<u8>, String> {
+    let mut stripped = module[..HEADER.len()].to_vec();
+    for section in sections(module)? {
+        if section.custom().is_some_and(|(section, _)| section == name) {
+            continue;
+        }
+        stripped.extend_from_slice(&module[section.range]);
+    }
+    Ok(stripped)
+}
+
+/// Encode a custom section, ready to be appended to a module
+pub fn custom_section(name: &str, contents: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    write_u32(&mut payload, name.len() as u32);
+    payload.extend_from_slice(name.as_bytes());
+    payload.extend_from_slice(contents);
+
+    let mut section = vec![CUSTOM];
+    write_u32(&mut section, payload.len() as u32);
+    section.extend_from_slice(&payload);
+    section
+}
+
+/// Read an unsigned LEB128 number
+fn read_u32(bytes: &[u8], offset: &mut usize) -> Result<u32, String> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let Some(&byte) = bytes.get(*offset) else {
+            return Err("unexpected end of the module".to_string());
+        };
+        *offset += 1;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("section size is too large".to_string())
+}
+
+/// Write an unsigned LEB128 number
+fn write_u32(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{sections, Error};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{de::Error as _, Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::Path};
+
+/// The custom section a module's signature is kept in
+pub const SECTION: &str = "reso.signature";
+
+/// The contents of the signature section
+///
+/// The signature covers the whole module with the signature section left out, so signing doesn't
+/// depend on where the section ends up.
+#[derive(Serialize, Deserialize)]
+struct SignatureSection {
+    /// Who signed the module, usually an MLS id. Used to pick the key to verify with.
+    signer: String,
+    /// The hex-encoded ed25519 signature
+    signature: String,
+}
+
+/// Why a module couldn't be verified
+#[derive(Debug)]
+pub enum Problem {
+    /// The module or its signature section couldn't be parsed
+    Malformed(String),
+    /// The module has no signature section
+    Unsigned,
+    /// The module was signed by someone who isn't in the trusted keys
+    UnknownSigner(String),
+    /// The signature doesn't match the module, so it was changed after it was signed or it was
+    /// signed with a different key
+    BadSignature(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Malformed(problem) => write!(f, "{problem}"),
+            Problem::Unsigned => write!(f, "the module is not signed"),
+            Problem::UnknownSigner(signer) => {
+                write!(
+                    f,
+                    "the module is signed by {signer:?}, which is not trusted"
+                )
+            }
+            Problem::BadSignature(signer) => write!(
+                f,
+                "the module claims to be signed by {signer:?}, but the signature doesn't match"
+            ),
+        }
+    }
+}
+
+/// The public keys of everyone whose modules we trust, keyed by signer
+///
+/// Read from a JSON file mapping each signer to their hex-encoded ed25519 public key, as in
+/// `{"ExampleMLS": "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c"}`.
+#[derive(Debug, Default)]
+pub struct TrustedKeys {
+    keys: BTreeMap<String, VerifyingKey>,
+}
+
+impl TrustedKeys {
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read(path).map_err(|source| Error::ReadInput {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let parse_error = |source| Error::ParseInput {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let encoded: BTreeMap<String, String> =
+            serde_json::from_slice(&contents).map_err(parse_error)?;
+        let mut keys = BTreeMap::new();
+        for (signer, key) in encoded {
+            let key = decode_key(&key)
+                .and_then(|key| VerifyingKey::from_bytes(&key).ok())
+                .ok_or_else(|| {
+                    parse_error(serde_json::Error::custom(format!(
+                        "the key for {signer:?} is not a hex-encoded ed25519 public key"
+                    )))
+                })?;
+            keys.insert(signer, key);
+        }
+        Ok(Self { keys })
+    }
+}
+
+/// Read a signing key: 32 random bytes, hex-encoded, such as the output of `openssl rand -hex 32`
+pub fn read_signing_key(path: &Path) -> Result<SigningKey, Error> {
+    let contents = std::fs::read_to_string(path).map_err(|source| Error::ReadInput {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let key = decode_key(contents.trim()).ok_or_else(|| Error::ParseInput {
+        path: path.to_path_buf(),
+        source: serde_json::Error::custom("expected 64 hex digits"),
+    })?;
+    Ok(SigningKey::from_bytes(&key))
+}
+
+/// Sign a module on behalf of `signer`, replacing any signature it already has
+pub fn sign(module: &[u8], signer: &str, key: &SigningKey) -> Result<Vec<u8>, Problem> {
+    let mut signed = sections::without_custom(module, SECTION).map_err(Problem::Malformed)?;
+    let signature = SignatureSection {
+        signer: signer.to_string(),
+        signature: hex::encode(key.sign(&signed).to_bytes()),
+    };
+    let contents = serde_json::to_vec(&signature).unwrap();
+    signed.extend(sections::custom_section(SECTION, &contents));
+    Ok(signed)
+}
+
+/// Check a module's signature against the trusted keys, returning who signed it
+///
+/// Compile the same bytes that were verified, rather than reading the module again, or it could
+/// be swapped for an unsigned one in between.
+pub fn verify(module: &[u8], trusted: &TrustedKeys) -> Result<String, Problem> {
+    let section = sections::find_custom(module, SECTION)
+        .map_err(Problem::Malformed)?
+        .ok_or(Problem::Unsigned)?;
+    let section: SignatureSection = serde_json::from_slice(section)
+        .map_err(|err| Problem::Malformed(format!("the signature section is not valid: {err}")))?;
+    let signature = hex::decode(&section.signature)
+        .ok()
+        .and_then(|signature| Signature::from_slice(&signature).ok())
+        .ok_or_else(|| {
+            Problem::Malformed("the signature is not hex-encoded ed25519".to_string())
+        })?;
+
+    let Some(key) = trusted.keys.get(&section.signer) else {
+        return Err(Problem::UnknownSigner(section.signer));
+    };
+    let unsigned = sections::without_custom(module, SECTION).map_err(Problem::Malformed)?;
+    match key.verify(&unsigned, &signature) {
+        Ok(()) => Ok(section.signer),
+        Err(_) => Err(Problem::BadSignature(section.signer)),
+    }
+}
+
+/// Decode a hex-encoded 32-byte key
+fn decode_key(key: &str) -> Option<[u8; 32]> {
+    hex::decode(key).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, verify, Problem, TrustedKeys, SECTION};
+    use crate::sections;
+    use ed25519_dalek::SigningKey;
+
+    fn module() -> Vec<u8> {
+        wat::parse_str(
+            r#"(module (memory (export "memory") 1) (data (i32.const 0) "ListPrice")
+                (func (export "validate")))"#,
+        )
+        .unwrap()
+    }
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn trusting(signer: &str, key: &SigningKey) -> TrustedKeys {
+        TrustedKeys {
+            keys: [(signer.to_string(), key.verifying_key())].into(),
+        }
+    }
+
+    #[test]
+    fn signed_modules_verify() {
+        let signed = sign(&module(), "ExampleMLS", &key(1)).unwrap();
+        assert_eq!(
+            verify(&signed, &trusting("ExampleMLS", &key(1))).unwrap(),
+            "ExampleMLS"
+        );
+        assert!(sections::find_custom(&signed, SECTION).unwrap().is_some());
+    }
+
+    #[test]
+    fn signing_again_replaces_the_signature() {
+        let signed = sign(&module(), "OtherMLS", &key(2)).unwrap();
+        let resigned = sign(&signed, "ExampleMLS", &key(1)).unwrap();
+        assert_eq!(
+            verify(&resigned, &trusting("ExampleMLS", &key(1))).unwrap(),
+            "ExampleMLS"
+        );
+    }
+
+    #[test]
+    fn tampered_modules_do_not_verify() {
+        let mut signed = sign(&module(), "ExampleMLS", &key(1)).unwrap();
+        let at = signed
+            .windows(9)
+            .position(|window| window == b"ListPrice")
+            .unwrap();
+        signed[at] = b'l';
+        assert!(matches!(
+            verify(&signed, &trusting("ExampleMLS", &key(1))),
+            Err(Problem::BadSignature(signer)) if signer == "ExampleMLS"
+        ));
+    }
+
+    #[test]
+    fn modules_signed_with_another_key_do_not_verify() {
+        let signed = sign(&module(), "ExampleMLS", &key(2)).unwrap();
+        assert!(matches!(
+            verify(&signed, &trusting("ExampleMLS", &key(1))),
+            Err(Problem::BadSignature(_))
+        ));
+    }
+
+    #[test]
+    fn modules_signed_by_someone_untrusted_do_not_verify() {
+        let signed = sign(&module(), "OtherMLS", &key(1)).unwrap();
+        assert!(matches!(
+            verify(&signed, &trusting("ExampleMLS", &key(1))),
+            Err(Problem::UnknownSigner(signer)) if signer == "OtherMLS"
+        ));
+    }
+
+    #[test]
+    fn unsigned_modules_do_not_verify() {
+        assert!(matches!(
+            verify(&module(), &trusting("ExampleMLS", &key(1))),
+            Err(Problem::Unsigned)
+        ));
+        assert!(matches!(
+            verify(b"(module)", &TrustedKeys::default()),
+            Err(Problem::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::trace::HostCall;
+use serde::Serialize;
+use std::{collections::BTreeMap, time::Duration};
+
+/// The size of a WebAssembly memory page
+const WASM_PAGE_SIZE: usize = 65536;
+
+/// Numbers describing how a module behaved during a run
+#[derive(Debug, Default, Serialize)]
+pub struct Stats {
+    /// How long it took to compile the module
+    pub compile_us: u64,
+    /// How long it took to link and instantiate the module
+    pub instantiate_us: u64,
+    /// How long the call to `validate` took, including host calls
+    pub validate_us: u64,
+    /// How much fuel `validate` consumed. Roughly, the number of wasm instructions executed.
+    pub fuel_consumed: Option<u64>,
+    /// The largest the module's linear memory got, in bytes
+    pub peak_memory_bytes: usize,
+    /// Calls to each host function, keyed by function name
+    pub host_calls: BTreeMap<String, HostCallStats>,
+}
+
+/// Totals for every call to a single host function
+#[derive(Debug, Default, Serialize)]
+pub struct HostCallStats {
+    /// The number of times the module called the function
+    pub calls: u64,
+    /// Bytes the host read out of the module's memory
+    pub bytes_read: u64,
+    /// Bytes the host wrote into the module's memory
+    pub bytes_written: u64,
+    /// Time spent in the host handling these calls
+    pub time_us: u64,
+}
+
+impl Stats {
+    /// Add a finished host call to the totals
+    pub fn record(&mut self, call: &HostCall, duration: Duration) {
+        let totals = self.host_calls.entry(call.function.clone()).or_default();
+        totals.calls += 1;
+        totals.bytes_read += call.read.iter().map(|read| read.len() as u64).sum::<u64>();
+        totals.bytes_written += call
+            .written
+            .as_ref()
+            .map_or(0, |written| written.len() as u64);
+        totals.time_us += duration.as_micros() as u64;
+    }
+
+    /// Print the statistics in a form meant for people
+    pub fn print_human(&self) {
+        println!("📊 Statistics");
+        println!("   compile      {}", format_us(self.compile_us));
+        println!("   instantiate  {}", format_us(self.instantiate_us));
+        println!("   validate     {}", format_us(self.validate_us));
+        if let Some(fuel) = self.fuel_consumed {
+            println!("   fuel         {fuel}");
+        }
+        println!(
+            "   peak memory  {} bytes ({} pages)",
+            self.peak_memory_bytes,
+            self.peak_memory_bytes / WASM_PAGE_SIZE
+        );
+
+        if self.host_calls.is_empty() {
+            return;
+        }
+        println!(
+            "   {:<20} {:>7} {:>10} {:>10} {:>10}",
+            "host call", "calls", "read", "written", "time"
+        );
+        for (function, totals) in &self.host_calls {
+            println!(
+                "   {:<20} {:>7} {:>10} {:>10} {:>10}",
+                format!("reso.{function}"),
+                totals.calls,
+                totals.bytes_read,
+                totals.bytes_written,
+                format_us(totals.time_us)
+            );
+        }
+    }
+}
+
+/// Track the module's memory as it grows. Every growth is allowed; we only watch.
+impl wasmtime::ResourceLimiter for Stats {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        self.peak_memory_bytes = self.peak_memory_bytes.max(desired);
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> anyhow::Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Format a number of microseconds as milliseconds
+fn format_us(us: u64) -> String {
+    format!("{:.3} ms", us as f64 / 1000.0)
+}
+
+/// The same, for wasmi.
+#[cfg(feature = "wasmi")]
+impl wasmi::ResourceLimiter for Stats {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> Result<bool, wasmi::errors::MemoryError> {
+        self.peak_memory_bytes = self.peak_memory_bytes.max(desired);
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> Result<bool, wasmi::errors::TableError> {
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stats;
+    use crate::trace::HostCall;
+    use std::time::Duration;
+
+    fn call(function: &str, read: &[&str], written: Option<&str>) -> HostCall {
+        HostCall {
+            function: function.to_string(),
+            args: Vec::new(),
+            read: read.iter().map(|read| read.to_string()).collect(),
+            written: written.map(str::to_string),
+            result: None,
+            error: None,
+            started_us: 0,
+            duration_us: 0,
+        }
+    }
+
+    #[test]
+    fn host_calls_are_totalled_by_function() {
+        let mut stats = Stats::default();
+        stats.record(
+            &call("data", &[], Some(r#"{"ListPrice":1}"#)),
+            Duration::from_micros(5),
+        );
+        stats.record(&call("data", &[], None), Duration::from_micros(2));
+        stats.record(
+            &call("error", &["ListPrice", "too low"], None),
+            Duration::from_micros(1),
+        );
+
+        let data = &stats.host_calls["data"];
+        assert_eq!(
+            (
+                data.calls,
+                data.bytes_read,
+                data.bytes_written,
+                data.time_us
+            ),
+            (2, 0, 15, 7)
+        );
+        let error = &stats.host_calls["error"];
+        assert_eq!(
+            (
+                error.calls,
+                error.bytes_read,
+                error.bytes_written,
+                error.time_us
+            ),
+            (1, 16, 0, 1)
+        );
+        assert_eq!(stats.host_calls.len(), 2);
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{
+    exit_code, quotas, read_catalog, read_module, read_overlays, read_related, read_schema,
+    run_layers, Args, EXIT_SUCCESS,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+};
+use webassembly_rules_poc::{
+    expanded_collections, history::History, listing_key, messages::Catalog, metadata::Metadata,
+    outcome::Outcome, schema::Schema, Context, Error, Validator,
+};
+
+/// The request couldn't be parsed as JSON
+const PARSE_ERROR: i64 = -32700;
+/// The request was JSON, but not a JSON-RPC request
+const INVALID_REQUEST: i64 = -32600;
+/// There's no method with the request's name
+const METHOD_NOT_FOUND: i64 = -32601;
+/// The method's parameters were missing or wrong, or named a module that isn't loaded
+const INVALID_PARAMS: i64 = -32602;
+/// The host panicked while handling the request
+const INTERNAL_ERROR: i64 = -32603;
+/// Compiling, verifying or running a module failed; the error's data has the exit code the
+/// command line would have used
+const MODULE_ERROR: i64 = -32000;
+
+/// Serve JSON-RPC 2.0 requests, one per line on stdin, until stdin closes
+///
+/// Responses are written to stdout, one per line, in the order the requests came in. Modules stay
+/// compiled between requests until they're unloaded, so a parent process can keep its rules warm.
+pub fn serve(args: &Args) -> Result<u8, Error> {
+    let schema = read_schema(args)?;
+    let mut server = Server {
+        args,
+        modules: BTreeMap::new(),
+        next_module: 1,
+        related: read_related(args, schema.as_ref())?,
+        catalog: read_catalog(args)?,
+        schema,
+        overlays: read_overlays(args)?,
+        history: args.history.as_deref().map(History::open).transpose()?,
+    };
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|source| Error::ReadInput {
+            path: PathBuf::from("<stdin>"),
+            source,
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(response) = server.handle(&line) else {
+            continue;
+        };
+        writeln!(stdout, "{response}")
+            .and_then(|()| stdout.flush())
+            .map_err(|source| Error::WriteOutput {
+                path: PathBuf::from("<stdout>"),
+                source,
+            })?;
+    }
+    Ok(EXIT_SUCCESS)
+}
+
+/// The modules a session has loaded, and everything from the command line that applies to all
+/// of them
+struct Server<'a> {
+    args: &'a Args,
+    /// Compiled modules and their paths, keyed by the id `load_module` handed out
+    modules: BTreeMap<u64, (String, Validator)>,
+    next_module: u64,
+    related: BTreeMap<String, String>,
+    catalog: Catalog,
+    schema: Option<Schema>,
+    /// The modules given with `--overlay`, run after every loaded module
+    overlays: Vec<(String, Validator)>,
+    history: Option<History>,
+}
+
+/// A JSON-RPC error, as it appears in a response
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(err: Error) -> Self {
+        Self {
+            code: MODULE_ERROR,
+            message: err.to_string(),
+            data: Some(json!({ "exit_code": exit_code(&err) })),
+        }
+    }
+}
+
+/// The parts of a request that don't depend on its method
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// The parameters of `load_module`
+#[derive(Deserialize)]
+struct LoadModule {
+    /// The module to compile
+    path: PathBuf,
+}
+
+/// The parameters of `validate`
+#[derive(Deserialize)]
+struct Validate {
+    /// The id `load_module` returned
+    module: u64,
+    /// The listing
+    data: Value,
+    /// The listing as it was before, if it's being edited. Taken from the history if there is one
+    /// and this isn't given.
+    #[serde(default)]
+    previous_data: Value,
+    /// The fields the caller says were edited
+    edited: Option<Vec<String>>,
+    /// Only report on these fields (and nested ones)
+    fields: Option<Vec<String>>,
+    /// Related resources, such as `Media`, keyed by name, on top of any from the command line
+    #[serde(default)]
+    related: BTreeMap<String, Value>,
+}
+
+/// The parameters of `unload`
+#[derive(Deserialize)]
+struct Unload {
+    /// The id `load_module` returned
+    module: u64,
+}
+
+/// What `validate` returns
+#[derive(Serialize)]
+struct Validated {
+    /// Everything the module said about the listing
+    outcome: Outcome,
+    /// Why `validate` trapped, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trap: Option<String>,
+}
+
+impl Server<'_> {
+    /// Handle a single line, returning the response to write, if there is one
+    ///
+    /// Notifications (requests without an id) are run, but never answered, as JSON-RPC asks.
+    fn handle(&mut self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => {
+                let error = RpcError::new(PARSE_ERROR, format!("Invalid JSON: {err}"));
+                return Some(response(Value::Null, Err(error)));
+            }
+        };
+        if request.is_array() {
+            let error = RpcError::new(INVALID_REQUEST, "Batches aren't supported");
+            return Some(response(Value::Null, Err(error)));
+        }
+
+        // Requests too broken to run are answered even without an id.
+        let id = request.get("id").cloned();
+        let request = match serde_json::from_value::<Request>(request) {
+            Ok(request) if request.jsonrpc == "2.0" => request,
+            Ok(_) => {
+                let error = RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"");
+                return Some(response(id.unwrap_or_default(), Err(error)));
+            }
+            Err(err) => {
+                let error = RpcError::new(INVALID_REQUEST, err.to_string());
+                return Some(response(id.unwrap_or_default(), Err(error)));
+            }
+        };
+
+        // A panic is a bug in the host, but it shouldn't take every other request down with it.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.call(&request.method, request.params)
+        }))
+        .unwrap_or_else(|panic| Err(RpcError::new(INTERNAL_ERROR, panic_message(panic))));
+        Some(response(id?, result))
+    }
+
+    /// Run a method
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "load_module" => self.load_module(parse_params(params)?),
+            "validate" => self.validate(parse_params(params)?),
+            "unload" => self.unload(parse_params(params)?),
+            // Lets the tests check that a panic is answered rather than ending the session.
+            #[cfg(debug_assertions)]
+            "debug_panic" => panic!("debug_panic was called"),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method '{method}'"),
+            )),
+        }
+    }
+
+    /// Verify and compile a module, returning the id to validate with and its metadata
+    fn load_module(&mut self, params: LoadModule) -> Result<Value, RpcError> {
+        let contents = read_module(self.args, &params.path)?;
+        let validator =
+            Validator::from_contents(self.args.runtime, &params.path, &contents, false)?;
+        let metadata = Metadata::from_contents(&params.path, &contents)?;
+
+        let module = self.next_module;
+        self.next_module += 1;
+        let name = params.path.to_string_lossy().into_owned();
+        self.modules.insert(module, (name, validator));
+        Ok(json!({ "module": module, "metadata": metadata }))
+    }
+
+    /// Validate a listing with a loaded module
+    fn validate(&self, params: Validate) -> Result<Value, RpcError> {
+        let Some((name, validator)) = self.modules.get(&params.module) else {
+            return Err(unknown_module(params.module));
+        };
+        if let Some(schema) = &self.schema {
+            let mut checks = vec![
+                ("data".to_string(), schema.check(&params.data)),
+                (
+                    "previous_data".to_string(),
+                    schema.check(&params.previous_data),
+                ),
+            ];
+            checks.extend(params.related.iter().map(|(name, resource)| {
+                let mismatches = schema.check_related(name, resource);
+                (format!("related.{name}"), mismatches)
+            }));
+            for (path, mismatches) in checks {
+                if !mismatches.is_empty() {
+                    let path = PathBuf::from(path);
+                    return Err(Error::Schema { path, mismatches }.into());
+                }
+            }
+        }
+
+        let key = listing_key(&params.data);
+        let previous_data = match (&self.history, &key, &params.previous_data) {
+            (Some(history), Some(key), Value::Null) => {
+                let previous_data = history.previous(key)?.unwrap_or_default();
+                let mismatches = self
+                    .schema
+                    .as_ref()
+                    .map_or_else(Vec::new, |schema| schema.check(&previous_data));
+                if !mismatches.is_empty() {
+                    let path = history.path(key);
+                    return Err(Error::Schema { path, mismatches }.into());
+                }
+                previous_data
+            }
+            _ => params.previous_data,
+        };
+
+        let related: BTreeMap<_, _> = params
+            .related
+            .iter()
+            .map(|(name, value)| (name.clone(), serde_json::to_string(value).unwrap()))
+            .collect();
+        // Verbose output would end up amongst the responses, so there isn't any.
+        let context = || {
+            let context = Context::new(
+                serde_json::to_string(&params.data).unwrap(),
+                serde_json::to_string(&previous_data).unwrap(),
+                0,
+            )
+            .with_related(expanded_collections(&params.data))
+            .with_related(self.related.clone())
+            .with_related(related.clone())
+            .with_messages(self.catalog.clone())
+            .with_quotas(quotas(self.args));
+            match &params.edited {
+                Some(edited) => context.with_edited(edited.clone()),
+                None => context,
+            }
+        };
+
+        let finished = run_layers(name, validator, &self.overlays, context(), context)?;
+        let mut outcome = finished.context.outcome;
+        // Only listings that would have been accepted become the previous data for the next one.
+        if let (Some(history), Some(key)) = (&self.history, &key) {
+            if finished.trap.is_none() && !outcome.has_errors() {
+                history.record(key, &params.data)?;
+            }
+        }
+        if let Some(fields) = &params.fields {
+            outcome.retain_fields(fields);
+        }
+        let validated = Validated {
+            outcome,
+            trap: finished.trap.map(|trap| format!("{trap:#}")),
+        };
+        Ok(serde_json::to_value(validated).unwrap())
+    }
+
+    /// Forget a module
+    fn unload(&mut self, params: Unload) -> Result<Value, RpcError> {
+        match self.modules.remove(&params.module) {
+            Some(_) => Ok(Value::Null),
+            None => Err(unknown_module(params.module)),
+        }
+    }
+}
+
+/// Parse a method's parameters, which must be an object
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn unknown_module(module: u64) -> RpcError {
+    RpcError::new(INVALID_PARAMS, format!("No module {module} is loaded"))
+}
+
+/// What a panic said, if it said anything
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    let message = match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => return "Internal error".to_string(),
+        },
+    };
+    format!("Internal error: {message}")
+}
+
+/// Build a response to the request with the given id
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{quota::Quotas, Context, Error};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::Path};
+
+/// A single call from the module to one of the `reso.*` host functions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostCall {
+    /// The name of the host function, without the `reso.` prefix
+    pub function: String,
+    /// The raw arguments the module passed
+    pub args: Vec<i32>,
+    /// Strings the host read out of the module's memory, in the order they were read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub read: Vec<String>,
+    /// Bytes the host wrote into the module's memory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written: Option<String>,
+    /// The value the host returned to the module, if the function returns anything
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<i32>,
+    /// The reason the host call trapped, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Microseconds between calling `validate` and the start of this call
+    pub started_us: u64,
+    /// How many microseconds the host spent handling this call
+    pub duration_us: u64,
+}
+
+impl HostCall {
+    /// Start a record for a call to `function` with the given arguments
+    pub fn new(function: &str, args: &[i32]) -> Self {
+        Self {
+            function: function.to_string(),
+            args: args.to_vec(),
+            read: Vec::new(),
+            written: None,
+            result: None,
+            error: None,
+            started_us: 0,
+            duration_us: 0,
+        }
+    }
+
+    /// Describe how this call differs from `other`, ignoring timing.
+    fn difference(&self, other: &HostCall) -> Option<String> {
+        if self.function != other.function || self.args != other.args {
+            return Some(format!("expected {self} but the module called {other}"));
+        }
+        if self.read != other.read {
+            return Some(format!(
+                "{self} passed {:?} but previously passed {:?}",
+                other.read, self.read
+            ));
+        }
+        if self.written != other.written {
+            return Some(format!(
+                "{self} was given {:?} but previously was given {:?}",
+                other.written, self.written
+            ));
+        }
+        if self.result != other.result {
+            return Some(format!(
+                "{self} returned {:?} but previously returned {:?}",
+                other.result, self.result
+            ));
+        }
+        if self.error != other.error {
+            return Some(format!(
+                "{self} failed with {:?} but previously failed with {:?}",
+                other.error, self.error
+            ));
+        }
+        None
+    }
+}
+
+impl fmt::Display for HostCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reso.{}(", self.function)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{arg}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Everything needed to re-run a validation and check that it behaves the same way
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Trace {
+    /// The host that recorded the trace
+    pub host: String,
+    /// The exact JSON string handed to the module by `reso.data`
+    pub data: String,
+    /// The exact JSON string handed to the module by `reso.previous_data`
+    pub previous_data: String,
+    /// The exact JSON strings handed to the module by `reso.related`, keyed by resource name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub related: BTreeMap<String, String>,
+    /// The fields the caller said were edited, handed to the module by `reso.changed_fields`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited: Option<Vec<String>>,
+    /// The quotas the module ran under, which decide where it traps if it makes too many calls
+    #[serde(default)]
+    pub quotas: Quotas,
+    /// Every host call the module made, in order
+    pub calls: Vec<HostCall>,
+    /// Why `validate` trapped, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trap: Option<String>,
+}
+
+impl Trace {
+    /// Turn a finished (recording) context into a trace
+    pub fn new(context: Context, trap: Option<&anyhow::Error>) -> Self {
+        Trace {
+            host: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            data: context.data,
+            previous_data: context.previous_data,
+            related: context.related,
+            edited: context.edited,
+            quotas: context.quotas,
+            calls: context.trace.unwrap_or_default(),
+            trap: trap.map(|err| format!("{err:#}")),
+        }
+    }
+
+    /// Read a trace from a JSON file
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read(path).map_err(|source| Error::ReadInput {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_slice(&contents).map_err(|source| Error::ParseInput {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Write the trace to a JSON file
+    pub fn to_file(&self, path: &Path) -> Result<(), Error> {
+        let contents = serde_json::to_vec_pretty(self).unwrap();
+        std::fs::write(path, contents).map_err(|source| Error::WriteOutput {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Compare a replayed run against this (recorded) trace, describing every way in which they
+    /// differ. Timing is expected to differ and is ignored.
+    pub fn divergences(&self, replayed: &Trace) -> Vec<String> {
+        let mut divergences = Vec::new();
+
+        for (i, (expected, actual)) in self.calls.iter().zip(&replayed.calls).enumerate() {
+            if let Some(difference) = expected.difference(actual) {
+                divergences.push(format!("call {i}: {difference}"));
+            }
+        }
+        for (i, call) in self.calls.iter().enumerate().skip(replayed.calls.len()) {
+            divergences.push(format!(
+                "call {i}: expected {call} but the module never made it"
+            ));
+        }
+        for (i, call) in replayed.calls.iter().enumerate().skip(self.calls.len()) {
+            divergences.push(format!(
+                "call {i}: the module made an unexpected call {call}"
+            ));
+        }
+
+        if self.trap != replayed.trap {
+            divergences.push(match (&self.trap, &replayed.trap) {
+                (Some(expected), Some(actual)) => {
+                    format!(
+                        "validate trapped with {actual:?} but previously trapped with {expected:?}"
+                    )
+                }
+                (Some(expected), None) => {
+                    format!("validate finished but previously trapped with {expected:?}")
+                }
+                (None, Some(actual)) => format!("validate trapped with {actual:?}"),
+                (None, None) => unreachable!(),
+            });
+        }
+
+        divergences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HostCall, Trace};
+    use crate::quota::Quotas;
+
+    fn trace(calls: Vec<HostCall>) -> Trace {
+        Trace {
+            host: "test".to_string(),
+            data: "{}".to_string(),
+            previous_data: "null".to_string(),
+            related: Default::default(),
+            edited: None,
+            quotas: Quotas::default(),
+            calls,
+            trap: None,
+        }
+    }
+
+    fn error(field: &str, message: &str) -> HostCall {
+        let mut call = HostCall::new("error", &[field.len() as i32, 0, message.len() as i32, 16]);
+        call.read = vec![field.to_string(), message.to_string()];
+        call
+    }
+
+    #[test]
+    fn identical_calls_do_not_diverge_whatever_their_timing() {
+        let recorded = trace(vec![error("ListPrice", "too low")]);
+        let mut call = error("ListPrice", "too low");
+        call.started_us = 100;
+        call.duration_us = 5;
+        assert!(recorded.divergences(&trace(vec![call])).is_empty());
+    }
+
+    #[test]
+    fn changed_calls_diverge() {
+        let recorded = trace(vec![error("ListPrice", "too low")]);
+        let replayed = trace(vec![error("ListPrice", "too big")]);
+        assert_eq!(
+            recorded.divergences(&replayed),
+            [
+                r#"call 0: reso.error(9, 0, 7, 16) passed ["ListPrice", "too big"] but previously passed ["ListPrice", "too low"]"#
+            ]
+        );
+
+        let replayed = trace(vec![error("ClosePrice", "too low")]);
+        assert_eq!(
+            recorded.divergences(&replayed),
+            ["call 0: expected reso.error(9, 0, 7, 16) but the module called reso.error(10, 0, 7, 16)"]
+        );
+    }
+
+    #[test]
+    fn missing_calls_diverge() {
+        let recorded = trace(vec![
+            error("ListPrice", "too low"),
+            error("City", "missing"),
+        ]);
+        let replayed = trace(vec![error("ListPrice", "too low")]);
+        assert_eq!(
+            recorded.divergences(&replayed),
+            ["call 1: expected reso.error(4, 0, 7, 16) but the module never made it"]
+        );
+    }
+
+    #[test]
+    fn extra_calls_diverge() {
+        let recorded = trace(vec![error("ListPrice", "too low")]);
+        let replayed = trace(vec![
+            error("ListPrice", "too low"),
+            error("City", "missing"),
+        ]);
+        assert_eq!(
+            recorded.divergences(&replayed),
+            ["call 1: the module made an unexpected call reso.error(4, 0, 7, 16)"]
+        );
+    }
+
+    #[test]
+    fn trapping_differently_diverges() {
+        let recorded = trace(Vec::new());
+        let mut replayed = trace(Vec::new());
+        replayed.trap = Some("unreachable".to_string());
+        assert_eq!(
+            recorded.divergences(&replayed),
+            [r#"validate trapped with "unreachable""#]
+        );
+    }
+}
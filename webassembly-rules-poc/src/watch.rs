@@ -0,0 +1,205 @@
+use crate::{validate_listings, Args};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+use webassembly_rules_poc::{outcome::Outcome, Error};
+
+/// How long to wait for a burst of changes to settle before re-running
+///
+/// Editors and compilers often write a file in several steps, and we only want to run once.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Run the validator, then re-run it every time one of its inputs changes
+///
+/// Only returns if watching fails. Problems with a single run, such as a module that no longer
+/// compiles, are printed and the watch carries on.
+pub fn watch(args: &Args) -> Result<u8, Error> {
+    let inputs = Inputs::new(args)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|err| watch_error(&inputs.dirs[0].0, err))?;
+    for (dir, _) in &inputs.dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|err| watch_error(dir, err))?;
+    }
+
+    let mut previous = None;
+    run_once(args, &mut previous);
+    loop {
+        println!("👀 Watching for changes; press Ctrl-C to stop");
+        let changed = wait_for_change(&receiver, &inputs)?;
+        println!();
+        println!("🔁 {} changed, re-running", changed.display());
+        run_once(args, &mut previous);
+    }
+}
+
+/// The files a run depends on, as the directories they live in
+///
+/// We watch directories rather than files, since many tools replace a file by writing a new one
+/// and renaming it over the old one, which would end a watch on the file itself.
+struct Inputs {
+    /// Each directory, with the file in it we care about, or `None` for every `.json` file
+    dirs: Vec<(PathBuf, Option<OsString>)>,
+}
+
+impl Inputs {
+    fn new(args: &Args) -> Result<Self, Error> {
+        let webassembly = args.webassembly.as_ref().expect("required by clap");
+        let mut inputs = Self { dirs: Vec::new() };
+        inputs.add(webassembly)?;
+        for overlay in &args.overlay {
+            inputs.add(overlay)?;
+        }
+        for data in &args.data {
+            inputs.add(data)?;
+        }
+        if let Some(previous_data) = &args.previous_data {
+            inputs.add(previous_data)?;
+        }
+        for (_, related) in &args.related {
+            inputs.add(related)?;
+        }
+        if let Some(messages) = &args.messages {
+            inputs.add(messages)?;
+        }
+        if let Some(schema) = &args.schema {
+            inputs.add(schema)?;
+        }
+        if let Some(trusted_keys) = &args.trusted_keys {
+            inputs.add(trusted_keys)?;
+        }
+        Ok(inputs)
+    }
+
+    /// Add a file, or every `.json` file in a directory
+    fn add(&mut self, path: &Path) -> Result<(), Error> {
+        let path = path.canonicalize().map_err(|source| Error::ReadInput {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let input = match (path.is_dir(), path.parent(), path.file_name()) {
+            (false, Some(dir), Some(file)) => (dir.to_path_buf(), Some(file.to_os_string())),
+            _ => (path, None),
+        };
+        if !self.dirs.contains(&input) {
+            self.dirs.push(input);
+        }
+        Ok(())
+    }
+
+    /// Whether a changed path is one of the inputs
+    fn contains(&self, path: &Path) -> bool {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        self.dirs.iter().any(|(dir, file)| {
+            dir == parent
+                && match file {
+                    Some(file) => file == name,
+                    None => path
+                        .extension()
+                        .is_some_and(|extension| extension == "json"),
+                }
+        })
+    }
+}
+
+/// Block until one of the inputs changes, returning the path that changed
+fn wait_for_change(
+    receiver: &mpsc::Receiver<notify::Result<notify::Event>>,
+    inputs: &Inputs,
+) -> Result<PathBuf, Error> {
+    loop {
+        let event = receiver
+            .recv()
+            .expect("the watcher lives as long as the receiver")
+            .map_err(|err| watch_error(&inputs.dirs[0].0, err))?;
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+        let Some(changed) = event.paths.into_iter().find(|path| inputs.contains(path)) else {
+            continue;
+        };
+
+        thread::sleep(DEBOUNCE);
+        while receiver.try_recv().is_ok() {}
+        return Ok(changed);
+    }
+}
+
+/// Validate every listing, then show how each outcome changed since the previous run
+///
+/// A run that fails leaves the previous outcomes alone, so the next successful run is compared
+/// with the last one that worked.
+fn run_once(args: &Args, previous: &mut Option<BTreeMap<String, Outcome>>) {
+    let reports = match validate_listings(args) {
+        Ok(reports) => reports,
+        Err(err) => {
+            eprintln!("{err}");
+            return;
+        }
+    };
+
+    if let Some(previous) = previous {
+        println!("📝 Changes since the last run");
+        let mut unchanged = true;
+        for report in &reports {
+            if !report.schema_mismatches.is_empty() {
+                continue;
+            }
+            let before = previous.get(&report.listing).cloned().unwrap_or_default();
+            let changes = report.outcome.changes_since(&before);
+            if changes.is_empty() {
+                continue;
+            }
+            unchanged = false;
+            if reports.len() > 1 {
+                println!("📄 {}", report.listing);
+            }
+            for change in changes {
+                println!("{change}");
+            }
+        }
+        if unchanged {
+            println!("   (none)");
+        }
+    }
+
+    // A listing that didn't match the schema wasn't run, so it keeps the outcome it had.
+    let mut outcomes = BTreeMap::new();
+    for report in reports {
+        let outcome = match report.schema_mismatches.is_empty() {
+            true => report.outcome,
+            false => previous
+                .as_ref()
+                .and_then(|previous| previous.get(&report.listing))
+                .cloned()
+                .unwrap_or_default(),
+        };
+        outcomes.insert(report.listing, outcome);
+    }
+    *previous = Some(outcomes);
+}
+
+/// Describe a failure to watch the inputs
+fn watch_error(path: &Path, err: notify::Error) -> Error {
+    let path = err
+        .paths
+        .first()
+        .map_or(path, PathBuf::as_path)
+        .to_path_buf();
+    Error::ReadInput {
+        path,
+        source: io::Error::other(err),
+    }
+}
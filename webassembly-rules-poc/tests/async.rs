@@ -0,0 +1,60 @@
+#![cfg(feature = "async")]
+
+use std::{path::Path, time::Duration};
+use webassembly_rules_poc::{AsyncValidator, Context};
+
+/// A module that reports an error on `ListPrice` for every listing
+const MODULE: &str = r#"
+(module
+  (import "reso" "error" (func $error (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListPricetoo low")
+  (func (export "validate")
+    (call $error (i32.const 9) (i32.const 0) (i32.const 7) (i32.const 9))))
+"#;
+
+/// A module that never finishes
+const LOOP: &str = r#"
+(module
+  (func (export "validate")
+    (loop $forever (br $forever))))
+"#;
+
+fn context() -> Context {
+    Context::new(r#"{"ListPrice":1}"#.to_string(), "null".to_string(), 0)
+}
+
+#[tokio::test]
+async fn validates_a_listing() {
+    let validator =
+        AsyncValidator::from_contents(Path::new("error.wat"), MODULE.as_bytes(), false).unwrap();
+
+    // The same compiled module can run any number of validations at once.
+    let (first, second) =
+        tokio::join!(validator.validate(context()), validator.validate(context()));
+    for finished in [first.unwrap(), second.unwrap()] {
+        assert!(finished.trap.is_none());
+        let errors = &finished.context.outcome.fields["ListPrice"].errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "too low");
+    }
+}
+
+#[tokio::test]
+async fn a_looping_module_can_be_timed_out() {
+    let validator =
+        AsyncValidator::from_contents(Path::new("loop.wat"), LOOP.as_bytes(), false).unwrap();
+
+    // On a single-threaded runtime, the timer only gets to fire because the module yields.
+    let validated =
+        tokio::time::timeout(Duration::from_millis(100), validator.validate(context())).await;
+    assert!(validated.is_err());
+}
+
+#[test]
+fn compile_errors_name_the_module() {
+    let err = AsyncValidator::from_contents(Path::new("broken.wat"), b"(module", false)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("broken.wat"), "{err}");
+}
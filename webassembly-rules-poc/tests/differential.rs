@@ -0,0 +1,77 @@
+#![cfg(feature = "wasmi")]
+
+use serde_json::{json, Value};
+use std::{path::Path, process::Command};
+
+/// The module rcp19-to-wasm generates from its example rules
+const RCP19: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../wasm/rcp19.wasm");
+
+/// Validate listings that between them break and satisfy every one of the example rules
+fn run(name: &str, args: &[&str]) -> (Option<i32>, Value) {
+    let dir = std::env::temp_dir().join(format!("differential-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, listing) in [
+        (
+            "closed.json",
+            json!({ "ListPrice": 100, "ClosePrice": 90, "MlsStatus": "Closed" }),
+        ),
+        (
+            "free.json",
+            json!({ "ListPrice": 0, "ClosePrice": 90, "MlsStatus": "Active" }),
+        ),
+    ] {
+        std::fs::write(dir.join(name), listing.to_string()).unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"))
+        .arg("--webassembly")
+        .arg(Path::new(RCP19))
+        .arg("--data")
+        .arg(&dir)
+        .args(["--format", "json"])
+        .args(args)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    (
+        output.status.code(),
+        serde_json::from_slice(&output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn the_runtimes_agree_on_the_example_rules() {
+    let (code, report) = run("agree", &["--differential"]);
+
+    assert_eq!(code, Some(7));
+    let listings = report["listings"].as_array().unwrap();
+    assert_eq!(listings.len(), 2);
+    for listing in listings {
+        assert!(listing.get("disagreements").is_none(), "{listing}");
+    }
+    assert_eq!(
+        listings[1]["outcome"]["fields"]["ListPrice"]["errors"][0]["message"],
+        "List price must be greater than $0"
+    );
+}
+
+#[test]
+fn wasmi_gives_the_same_outcomes_as_wasmtime() {
+    let (wasmtime_code, wasmtime) = run("wasmtime", &["--runtime", "wasmtime"]);
+    let (wasmi_code, wasmi) = run("wasmi", &["--runtime", "wasmi"]);
+
+    assert_eq!(wasmi_code, wasmtime_code);
+    let outcomes = |report: &Value| {
+        report["listings"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|listing| listing["outcome"].clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(outcomes(&wasmi), outcomes(&wasmtime));
+    assert_eq!(
+        wasmi["listings"][0]["outcome"]["fields"]["ClosePrice"]["required"],
+        true
+    );
+}
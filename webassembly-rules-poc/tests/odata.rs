@@ -0,0 +1,189 @@
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+};
+use webassembly_rules_poc::{odata, Error};
+
+/// A stand-in for a RESO Web API server, answering each path with a canned page
+struct StandIn {
+    url: String,
+    /// The `Authorization` header of every request, in order
+    authorizations: Arc<Mutex<Vec<Option<String>>>>,
+}
+
+impl StandIn {
+    /// Serve the pages built by `pages`, which is given the server's base URL
+    fn start(pages: impl FnOnce(&str) -> BTreeMap<&'static str, Value>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let pages = pages(&url);
+        let authorizations = Arc::new(Mutex::new(Vec::new()));
+
+        let seen = authorizations.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line.split(' ').nth(1).unwrap().to_string();
+
+                let mut authorization = None;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.strip_prefix("Authorization: ") {
+                        authorization = Some(value.trim().to_string());
+                    }
+                }
+                seen.lock().unwrap().push(authorization);
+
+                let (status, body) = match pages.get(path.as_str()) {
+                    Some(page) => ("200 OK", page.to_string()),
+                    None => ("404 Not Found", "{}".to_string()),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        Self {
+            url,
+            authorizations,
+        }
+    }
+}
+
+#[test]
+fn follows_next_links_across_pages() {
+    let server = StandIn::start(|url| {
+        BTreeMap::from([
+            (
+                "/Property",
+                json!({
+                    "@odata.context": format!("{url}/$metadata#Property"),
+                    "value": [{"ListingKey": "L1"}, {"ListingKey": "L2"}],
+                    "@odata.nextLink": format!("{url}/Property?$skip=2"),
+                }),
+            ),
+            (
+                "/Property?$skip=2",
+                json!({
+                    "value": [{"ListingKey": "L3"}],
+                    "@odata.nextLink": "Property?$skip=3",
+                }),
+            ),
+            ("/Property?$skip=3", json!({"value": [{"ListPrice": 1}]})),
+        ])
+    });
+
+    let entities = odata::fetch(&format!("{}/Property", server.url), Some("secret")).unwrap();
+    let names: Vec<_> = entities
+        .iter()
+        .enumerate()
+        .map(|(i, entity)| odata::entity_name(entity, "Property", i))
+        .collect();
+    assert_eq!(names, ["L1", "L2", "L3", "Property[3]"]);
+    assert_eq!(
+        *server.authorizations.lock().unwrap(),
+        vec![Some("Bearer secret".to_string()); 3]
+    );
+}
+
+#[test]
+fn does_not_send_the_token_to_other_servers() {
+    let other = StandIn::start(|_| {
+        BTreeMap::from([("/Property", json!({"value": [{"ListingKey": "L2"}]}))])
+    });
+    let other_url = other.url.clone();
+    let server = StandIn::start(move |_| {
+        BTreeMap::from([(
+            "/Property",
+            json!({
+                "value": [{"ListingKey": "L1"}],
+                "@odata.nextLink": format!("{other_url}/Property"),
+            }),
+        )])
+    });
+
+    let entities = odata::fetch(&format!("{}/Property", server.url), Some("secret")).unwrap();
+    assert_eq!(entities.len(), 2);
+    assert_eq!(
+        *server.authorizations.lock().unwrap(),
+        [Some("Bearer secret".to_string())]
+    );
+    assert_eq!(*other.authorizations.lock().unwrap(), [None]);
+}
+
+#[test]
+fn stops_when_a_next_link_loops() {
+    let server = StandIn::start(|url| {
+        BTreeMap::from([(
+            "/Property",
+            json!({"value": [], "@odata.nextLink": format!("{url}/Property")}),
+        )])
+    });
+
+    let err = odata::fetch(&format!("{}/Property", server.url), None).unwrap_err();
+    assert!(matches!(err, Error::Fetch { .. }), "{err}");
+}
+
+#[test]
+fn reports_responses_that_are_not_collections() {
+    let server = StandIn::start(|_| BTreeMap::from([("/Property('L1')", json!({"ListPrice": 1}))]));
+
+    let err = odata::fetch(&format!("{}/Property('L1')", server.url), None).unwrap_err();
+    assert!(err.to_string().contains("not an OData collection"), "{err}");
+    let err = odata::fetch(&format!("{}/Missing", server.url), None).unwrap_err();
+    assert!(err.to_string().contains("404"), "{err}");
+}
+
+#[test]
+fn bare_listings_are_not_pages() {
+    let listing = json!({"ListingKey": "L1", "ListPrice": 1});
+    assert_eq!(
+        odata::Page::from_json(listing.clone()).unwrap_err(),
+        listing
+    );
+
+    let page = odata::Page::from_json(json!({"value": [listing]})).unwrap();
+    assert_eq!(page.entities.len(), 1);
+    assert!(page.next_link.is_none());
+
+    let page = odata::Page::from_json(json!({
+        "@odata.context": "$metadata#Property",
+        "@odata.nextLink": "https://example.com/Property?$skip=1",
+        "value": [listing],
+    }))
+    .unwrap();
+    assert_eq!(page.entities.len(), 1);
+    assert_eq!(
+        page.next_link.as_deref(),
+        Some("https://example.com/Property?$skip=1")
+    );
+}
+
+#[test]
+fn listings_with_a_value_field_are_not_pages() {
+    for listing in [
+        json!({"ListingKey": "L1", "value": "High"}),
+        json!({"ListingKey": "L1", "value": [1, 2]}),
+        json!({"value": {"Amount": 1}}),
+    ] {
+        assert_eq!(
+            odata::Page::from_json(listing.clone()).unwrap_err(),
+            listing
+        );
+    }
+}
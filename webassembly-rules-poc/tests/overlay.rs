@@ -0,0 +1,105 @@
+use serde_json::{json, Value};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// A module that reports an error on `ListPrice` and then traps
+const TRAPS: &str = r#"
+(module
+  (import "reso" "error" (func $error (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListPricetoo low")
+  (func (export "validate")
+    (call $error (i32.const 9) (i32.const 0) (i32.const 7) (i32.const 9))
+    unreachable))
+"#;
+
+/// A module that warns on `ListingKey` for every listing
+const WARNS: &str = r#"
+(module
+  (import "reso" "warn" (func $warn (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListingKeyoverlaid")
+  (func (export "validate")
+    (call $warn (i32.const 10) (i32.const 0) (i32.const 8) (i32.const 10))))
+"#;
+
+/// A directory of its own for a test, removed and recreated so it starts out empty
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("overlay-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Validate a listing with `base` and `overlay` layered on top, returning the exit code and the
+/// listing's JSON report
+fn run(dir: &Path, base: &str, overlay: &str) -> (Option<i32>, Value) {
+    let (base_path, overlay_path, listing) = (
+        dir.join("base.wat"),
+        dir.join("overlay.wat"),
+        dir.join("listing.json"),
+    );
+    std::fs::write(&base_path, base).unwrap();
+    std::fs::write(&overlay_path, overlay).unwrap();
+    std::fs::write(&listing, json!({ "ListingKey": "1" }).to_string()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"))
+        .arg("--webassembly")
+        .arg(&base_path)
+        .arg("--overlay")
+        .arg(&overlay_path)
+        .arg("--data")
+        .arg(&listing)
+        .args(["--format", "json"])
+        .output()
+        .unwrap();
+    let mut report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    (output.status.code(), report["listings"][0].take())
+}
+
+#[test]
+fn a_trap_in_the_base_layer_stops_before_the_overlays() {
+    let dir = temp_dir("base-trap");
+    let (code, report) = run(&dir, TRAPS, WARNS);
+    let base = dir.join("base.wat");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(code, Some(5));
+    let trap = report["trap"].as_str().unwrap();
+    assert!(
+        trap.starts_with(&format!("in {}", base.display())),
+        "{trap}"
+    );
+    let fields = &report["outcome"]["fields"];
+    assert_eq!(
+        fields["ListPrice"]["errors"],
+        json!([{ "message": "too low", "module": base }])
+    );
+    assert!(fields.get("ListingKey").is_none());
+}
+
+#[test]
+fn a_trap_in_an_overlay_keeps_what_the_base_layer_said() {
+    let dir = temp_dir("overlay-trap");
+    let (code, report) = run(&dir, WARNS, TRAPS);
+    let (base, overlay) = (dir.join("base.wat"), dir.join("overlay.wat"));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(code, Some(5));
+    let trap = report["trap"].as_str().unwrap();
+    assert!(
+        trap.starts_with(&format!("in {}", overlay.display())),
+        "{trap}"
+    );
+    let fields = &report["outcome"]["fields"];
+    assert_eq!(
+        fields["ListingKey"]["warnings"],
+        json!([{ "message": "overlaid", "module": base }])
+    );
+    assert_eq!(
+        fields["ListPrice"]["errors"],
+        json!([{ "message": "too low", "module": overlay }])
+    );
+}
@@ -0,0 +1,72 @@
+use serde_json::{json, Value};
+use std::process::Command;
+
+/// A module that reports whatever `reso.related` gives it for `Media` as an error on `Media`
+const MODULE: &str = r#"
+(module
+  (import "reso" "related" (func $related (param i32 i32 i32 i32) (result i32)))
+  (import "reso" "error" (func $error (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "Media")
+  (func (export "validate")
+    (local $len i32)
+    (local.set $len (call $related (i32.const 5) (i32.const 0) (i32.const 1024) (i32.const 1024)))
+    (call $error (i32.const 5) (i32.const 0) (local.get $len) (i32.const 1024))))
+"#;
+
+/// Validate a listing, giving the module each related resource, and return what it was given
+/// for `Media`
+fn media(name: &str, listing: Value, related: &[(&str, Value)]) -> String {
+    let dir = std::env::temp_dir().join(format!("related-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = dir.join("module.wat");
+    std::fs::write(&module, MODULE).unwrap();
+    let data = dir.join("listing.json");
+    std::fs::write(&data, listing.to_string()).unwrap();
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"));
+    command
+        .arg("--webassembly")
+        .arg(&module)
+        .arg("--data")
+        .arg(&data)
+        .args(["--format", "json"]);
+    for (name, resource) in related {
+        let path = dir.join(format!("{name}.json"));
+        std::fs::write(&path, resource.to_string()).unwrap();
+        command
+            .arg("--related")
+            .arg(format!("{name}={}", path.display()));
+    }
+    let output = command.output().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let message = &report["listings"][0]["outcome"]["fields"]["Media"]["errors"][0]["message"];
+    message.as_str().unwrap().to_string()
+}
+
+#[test]
+fn resources_the_host_does_not_have_are_null() {
+    let rooms = json!([{ "RoomType": "Kitchen" }]);
+    assert_eq!(media("none", json!({}), &[("Rooms", rooms)]), "null");
+}
+
+#[test]
+fn expanded_collections_are_related_resources() {
+    let listing = json!({ "Media": [{ "MediaURL": "inline.jpg" }] });
+    assert_eq!(
+        media("inline", listing, &[]),
+        r#"[{"MediaURL":"inline.jpg"}]"#
+    );
+}
+
+#[test]
+fn related_files_take_precedence_over_expanded_collections() {
+    let listing = json!({ "Media": [{ "MediaURL": "inline.jpg" }] });
+    let related = json!([{ "MediaURL": "file.jpg" }, { "MediaURL": "other.jpg" }]);
+    assert_eq!(
+        media("file", listing, &[("Media", related)]),
+        r#"[{"MediaURL":"file.jpg"},{"MediaURL":"other.jpg"}]"#
+    );
+}
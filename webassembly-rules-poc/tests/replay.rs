@@ -0,0 +1,108 @@
+use serde_json::Value;
+use std::{path::PathBuf, process::Command};
+
+/// A module that reports an error on `ListPrice` for every listing
+const MODULE: &str = r#"
+(module
+  (import "reso" "error" (func $error (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListPricetoo low")
+  (func (export "validate")
+    (call $error (i32.const 9) (i32.const 0) (i32.const 7) (i32.const 9))))
+"#;
+
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("replay-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn poc() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"))
+}
+
+/// Record a trace of the module, let `tamper` change it, and replay the module against it
+fn replay_tampered(name: &str, tamper: impl FnOnce(&mut Vec<Value>)) -> Option<i32> {
+    let module = temp_file(&format!("{name}.wat"), MODULE);
+    let data = temp_file(&format!("{name}.json"), r#"{"ListPrice": 1}"#);
+    let trace = std::env::temp_dir().join(format!("replay-{}-{name}.trace", std::process::id()));
+
+    let recorded = poc()
+        .arg("--webassembly")
+        .arg(&module)
+        .arg("--data")
+        .arg(&data)
+        .arg("--record")
+        .arg(&trace)
+        .output()
+        .unwrap();
+    assert_eq!(recorded.status.code(), Some(7));
+
+    let mut contents: Value = serde_json::from_slice(&std::fs::read(&trace).unwrap()).unwrap();
+    tamper(contents["calls"].as_array_mut().unwrap());
+    std::fs::write(&trace, contents.to_string()).unwrap();
+
+    let replayed = poc()
+        .arg("replay")
+        .arg("--webassembly")
+        .arg(&module)
+        .arg("--trace")
+        .arg(&trace)
+        .output()
+        .unwrap();
+    for path in [module, data, trace] {
+        std::fs::remove_file(path).unwrap();
+    }
+    replayed.status.code()
+}
+
+#[test]
+fn an_untouched_trace_replays_cleanly() {
+    assert_eq!(replay_tampered("untouched", |_| {}), Some(0));
+}
+
+#[test]
+fn a_changed_call_diverges() {
+    let code = replay_tampered("changed", |calls| {
+        calls[0]["read"][1] = Value::from("too high");
+    });
+    assert_eq!(code, Some(6));
+}
+
+#[test]
+fn a_missing_call_diverges() {
+    let code = replay_tampered("missing", |calls| {
+        let call = calls[0].clone();
+        calls.push(call);
+    });
+    assert_eq!(code, Some(6));
+}
+
+#[test]
+fn an_extra_call_diverges() {
+    let code = replay_tampered("extra", |calls| calls.clear());
+    assert_eq!(code, Some(6));
+}
+
+#[test]
+fn recording_a_batch_is_a_usage_error() {
+    let module = temp_file("batch.wat", MODULE);
+    let data = temp_file("batch.json", r#"{"ListPrice": 1}"#);
+    let trace = std::env::temp_dir().join(format!("replay-{}-batch.trace", std::process::id()));
+
+    let output = poc()
+        .arg("--webassembly")
+        .arg(&module)
+        .arg("--data")
+        .arg(&data)
+        .arg(&data)
+        .arg("--record")
+        .arg(&trace)
+        .output()
+        .unwrap();
+    for path in [module, data] {
+        std::fs::remove_file(path).unwrap();
+    }
+    assert_eq!(output.status.code(), Some(12));
+    assert!(!trace.exists());
+}
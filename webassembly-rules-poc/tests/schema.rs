@@ -0,0 +1,156 @@
+use serde_json::{json, Value};
+use std::{path::PathBuf, process::Command};
+
+/// A module that accepts every listing
+const MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "validate")))
+"#;
+
+/// A directory of its own for a test, removed and recreated so it starts out empty
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("schema-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn a_listing_of_the_wrong_types_does_not_stop_the_batch() {
+    let dir = temp_dir("batch");
+    let module = dir.join("module.wat");
+    std::fs::write(&module, MODULE).unwrap();
+    let listings = dir.join("listings");
+    let history = dir.join("history");
+    std::fs::create_dir_all(&listings).unwrap();
+    for (name, listing) in [
+        ("1.json", json!({ "ListingKey": "1", "ListPrice": 1 })),
+        (
+            "2.json",
+            json!({ "ListingKey": "2", "ModificationTimestamp": "2023-08-14T09:30:0é" }),
+        ),
+        ("3.json", json!({ "ListingKey": "3", "ListPrice": 3 })),
+    ] {
+        std::fs::write(listings.join(name), listing.to_string()).unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"))
+        .arg("--webassembly")
+        .arg(&module)
+        .arg("--data")
+        .arg(&listings)
+        .arg("--history")
+        .arg(&history)
+        .args(["--check-schema", "--format", "json"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(11));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let listings = report["listings"].as_array().unwrap();
+    assert_eq!(listings.len(), 3);
+    assert!(listings[0].get("schema_mismatches").is_none());
+    assert_eq!(
+        listings[1]["schema_mismatches"][0]["path"],
+        "ModificationTimestamp"
+    );
+    assert_eq!(listings[1]["schema_mismatches"][0]["expected"], "Timestamp");
+    assert!(listings[2].get("schema_mismatches").is_none());
+
+    // Only the listings that were validated become history
+    assert_eq!(std::fs::read_dir(&history).unwrap().count(), 2);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn previous_data_from_the_history_is_checked_too() {
+    let dir = temp_dir("history");
+    let module = dir.join("module.wat");
+    std::fs::write(&module, MODULE).unwrap();
+    let listings = dir.join("listings");
+    let history = dir.join("history");
+    std::fs::create_dir_all(&listings).unwrap();
+    std::fs::create_dir_all(&history).unwrap();
+    for (name, listing) in [
+        ("1.json", json!({ "ListingKey": "1", "ListPrice": 1 })),
+        ("2.json", json!({ "ListingKey": "2", "ListPrice": 2 })),
+    ] {
+        std::fs::write(listings.join(name), listing.to_string()).unwrap();
+    }
+    // Left behind by a run without --check-schema, say
+    let stale = json!({ "ListingKey": "1", "ListPrice": "cheap" });
+    std::fs::write(history.join("1.json"), stale.to_string()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"))
+        .arg("--webassembly")
+        .arg(&module)
+        .arg("--data")
+        .arg(&listings)
+        .arg("--history")
+        .arg(&history)
+        .args(["--check-schema", "--format", "json"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(11));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&history.join("1.json").to_string_lossy().into_owned()),
+        "{stderr}"
+    );
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let listings = report["listings"].as_array().unwrap();
+    assert_eq!(listings[0]["schema_mismatches"][0]["path"], "ListPrice");
+    assert!(listings[1].get("schema_mismatches").is_none());
+
+    // The stale version isn't replaced, since the listing wasn't validated
+    let kept: Value =
+        serde_json::from_slice(&std::fs::read(history.join("1.json")).unwrap()).unwrap();
+    assert_eq!(kept, stale);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn related_resources_are_checked_as_the_listing_s_collections() {
+    let dir = temp_dir("related");
+    let module = dir.join("module.wat");
+    std::fs::write(&module, MODULE).unwrap();
+    let listing = dir.join("listing.json");
+    std::fs::write(&listing, json!({ "ListingKey": "1" }).to_string()).unwrap();
+    let media = dir.join("media.json");
+    std::fs::write(
+        &media,
+        json!([{ "MediaURL": "a.jpg" }, { "MediaURL": 2 }]).to_string(),
+    )
+    .unwrap();
+    // Not a collection the listing has, so there are no types to check it against
+    let open_house = dir.join("open-house.json");
+    std::fs::write(&open_house, json!([{ "MediaURL": 2 }]).to_string()).unwrap();
+
+    let run = |related: &[(&str, &PathBuf)]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"));
+        command
+            .arg("--webassembly")
+            .arg(&module)
+            .arg("--data")
+            .arg(&listing)
+            .arg("--check-schema");
+        for (name, path) in related {
+            command
+                .arg("--related")
+                .arg(format!("{name}={}", path.display()));
+        }
+        command.output().unwrap()
+    };
+
+    let output = run(&[("Media", &media)]);
+    assert_eq!(output.status.code(), Some(11));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("media.json"), "{stderr}");
+    assert!(stderr.contains("Media[1].MediaURL"), "{stderr}");
+
+    let output = run(&[("OpenHouse", &open_house)]);
+    assert_eq!(output.status.code(), Some(0));
+    std::fs::remove_dir_all(dir).unwrap();
+}
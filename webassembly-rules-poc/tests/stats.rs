@@ -0,0 +1,50 @@
+use serde_json::{json, Value};
+use std::process::Command;
+
+/// A module that grows its memory to two pages, reads the data, and reports two errors
+const MODULE: &str = r#"
+(module
+  (import "reso" "data" (func $data (param i32 i32) (result i32)))
+  (import "reso" "error" (func $error (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListPricetoo lowtoo high")
+  (func (export "validate")
+    (drop (memory.grow (i32.const 1)))
+    (drop (call $data (i32.const 1024) (i32.const 1024)))
+    (call $error (i32.const 9) (i32.const 0) (i32.const 7) (i32.const 9))
+    (call $error (i32.const 9) (i32.const 0) (i32.const 8) (i32.const 16))))
+"#;
+
+#[test]
+fn stats_total_every_host_call_of_a_run() {
+    let dir = std::env::temp_dir().join(format!("stats-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = dir.join("module.wat");
+    std::fs::write(&module, MODULE).unwrap();
+    let listing = dir.join("listing.json");
+    let data = json!({ "ListPrice": 1 }).to_string();
+    std::fs::write(&listing, &data).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"))
+        .arg("--webassembly")
+        .arg(&module)
+        .arg("--data")
+        .arg(&listing)
+        .args(["--stats", "json", "--format", "json"])
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(output.status.code(), Some(7));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let stats = &report["listings"][0]["stats"];
+    assert_eq!(stats["peak_memory_bytes"], 2 * 65536);
+    assert_eq!(stats["host_calls"]["data"]["calls"], 1);
+    assert_eq!(stats["host_calls"]["data"]["bytes_written"], data.len());
+    assert_eq!(stats["host_calls"]["error"]["calls"], 2);
+    assert_eq!(
+        stats["host_calls"]["error"]["bytes_read"],
+        "ListPricetoo low".len() + "ListPricetoo high".len()
+    );
+    assert_eq!(stats["host_calls"]["error"]["bytes_written"], 0);
+}
@@ -0,0 +1,257 @@
+use serde_json::{json, Value};
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// A module that reports an error on `ListPrice` for every listing
+const MODULE: &str = r#"
+(module
+  (import "reso" "error" (func $error (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListPricetoo low")
+  (func (export "validate")
+    (call $error (i32.const 9) (i32.const 0) (i32.const 7) (i32.const 9))))
+"#;
+
+/// A module that warns on `ListingKey` when there's no previous data
+const FIRST_SEEN: &str = r#"
+(module
+  (import "reso" "previous_data" (func $previous_data (param i32 i32) (result i32)))
+  (import "reso" "warn" (func $warn (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListingKeyfirst seen")
+  (func (export "validate")
+    (if (i32.eq (call $previous_data (i32.const 0) (i32.const 0)) (i32.const 4))
+      (then (call $warn (i32.const 10) (i32.const 0) (i32.const 10) (i32.const 10))))))
+"#;
+
+/// Write the module to a file of its own, since `load_module` takes a path
+fn module_file(name: &str) -> PathBuf {
+    write_module(name, MODULE)
+}
+
+fn write_module(name: &str, module: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("stdio-{}-{name}.wat", std::process::id()));
+    std::fs::write(&path, module).unwrap();
+    path
+}
+
+/// Send every line to a `--stdio` session, close its stdin, and collect its responses
+fn session(lines: &[String]) -> Vec<Value> {
+    session_with(&[], lines)
+}
+
+/// Like `session`, with more arguments on the command line
+fn session_with(args: &[&std::ffi::OsStr], lines: &[String]) -> Vec<Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"))
+        .arg("--stdio")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for line in lines {
+        writeln!(stdin, "{line}").unwrap();
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn request(id: impl Into<Value>, method: &str, params: Value) -> String {
+    json!({ "jsonrpc": "2.0", "id": id.into(), "method": method, "params": params }).to_string()
+}
+
+#[test]
+fn modules_stay_loaded_until_unloaded() {
+    let path = module_file("loaded");
+    let responses = session(&[
+        request(1, "load_module", json!({ "path": path })),
+        request(
+            2,
+            "validate",
+            json!({ "module": 1, "data": { "ListPrice": 1 } }),
+        ),
+        request(
+            3,
+            "validate",
+            json!({ "module": 1, "data": { "ListPrice": 2 } }),
+        ),
+        request(4, "unload", json!({ "module": 1 })),
+        request(5, "validate", json!({ "module": 1, "data": {} })),
+    ]);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(responses.len(), 5);
+    assert_eq!(responses[0]["id"], 1);
+    assert_eq!(responses[0]["result"]["module"], 1);
+    let errors = json!([{ "message": "too low" }]);
+    for response in &responses[1..3] {
+        assert_eq!(
+            response["result"]["outcome"]["fields"]["ListPrice"]["errors"],
+            errors
+        );
+        assert!(response["result"].get("trap").is_none());
+    }
+    assert_eq!(responses[3]["result"], Value::Null);
+    assert_eq!(responses[4]["error"]["code"], -32602);
+}
+
+#[test]
+fn bad_requests_get_errors_and_notifications_get_nothing() {
+    let path = module_file("errors");
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "load_module",
+        "params": { "path": path },
+    });
+    let responses = session(&[
+        "not json".to_string(),
+        notification.to_string(),
+        request("missing", "load_module", json!({ "path": "missing.wasm" })),
+        request(1, "frobnicate", json!({})),
+        request(2, "validate", json!({ "data": {} })),
+        json!({ "id": 3, "method": "unload" }).to_string(),
+        // The notification still loaded a module
+        request(4, "validate", json!({ "module": 1, "data": {} })),
+    ]);
+    std::fs::remove_file(path).unwrap();
+
+    let codes: Vec<_> = responses
+        .iter()
+        .map(|response| (response["id"].clone(), response["error"]["code"].clone()))
+        .collect();
+    assert_eq!(
+        codes,
+        [
+            (Value::Null, json!(-32700)),
+            (json!("missing"), json!(-32000)),
+            (json!(1), json!(-32601)),
+            (json!(2), json!(-32602)),
+            (json!(3), json!(-32600)),
+            (json!(4), Value::Null),
+        ]
+    );
+    assert_eq!(responses[1]["error"]["data"]["exit_code"], 1);
+}
+
+// `debug_panic` only exists in debug builds.
+#[cfg(debug_assertions)]
+#[test]
+fn panics_are_internal_errors_and_the_session_carries_on() {
+    let path = module_file("panic");
+    let responses = session(&[
+        request(1, "load_module", json!({ "path": path })),
+        request(2, "debug_panic", json!({})),
+        request(3, "validate", json!({ "module": 1, "data": {} })),
+    ]);
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[1]["id"], 2);
+    assert_eq!(responses[1]["error"]["code"], -32603);
+    assert_eq!(
+        responses[1]["error"]["message"],
+        "Internal error: debug_panic was called"
+    );
+    assert_eq!(
+        responses[2]["result"]["outcome"]["fields"]["ListPrice"]["errors"][0]["message"],
+        "too low"
+    );
+}
+
+#[test]
+fn overlays_run_after_every_loaded_module() {
+    let path = module_file("base");
+    let overlay = write_module("overlay", FIRST_SEEN);
+    let responses = session_with(
+        &["--overlay".as_ref(), overlay.as_os_str()],
+        &[
+            request(1, "load_module", json!({ "path": path })),
+            request(2, "validate", json!({ "module": 1, "data": {} })),
+        ],
+    );
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&overlay).unwrap();
+
+    let fields = &responses[1]["result"]["outcome"]["fields"];
+    assert_eq!(
+        fields["ListPrice"]["errors"][0]["module"],
+        path.to_string_lossy().as_ref()
+    );
+    assert_eq!(
+        fields["ListingKey"]["warnings"][0]["module"],
+        overlay.to_string_lossy().as_ref()
+    );
+}
+
+#[test]
+fn history_supplies_previous_data() {
+    let path = write_module("history", FIRST_SEEN);
+    let history = std::env::temp_dir().join(format!("stdio-{}-history", std::process::id()));
+    let validate = |id, data: Value| request(id, "validate", json!({ "module": 1, "data": data }));
+    let responses = session_with(
+        &["--history".as_ref(), history.as_os_str()],
+        &[
+            request(1, "load_module", json!({ "path": path })),
+            validate(2, json!({ "ListingKey": "A" })),
+            validate(3, json!({ "ListingKey": "A" })),
+            validate(4, json!({ "ListingKey": "B" })),
+            request(
+                5,
+                "validate",
+                json!({
+                    "module": 1,
+                    "data": { "ListingKey": "B" },
+                    "previous_data": { "ListingKey": "B" },
+                }),
+            ),
+        ],
+    );
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_dir_all(history).unwrap();
+
+    let first_seen: Vec<_> = responses[1..]
+        .iter()
+        .map(|response| response["result"]["outcome"]["fields"]["ListingKey"].is_object())
+        .collect();
+    assert_eq!(first_seen, [true, false, true, false]);
+}
+
+#[test]
+fn related_resources_in_a_request_are_checked_against_the_schema() {
+    let path = module_file("related-schema");
+    let validate = |id, related: Value| {
+        request(
+            id,
+            "validate",
+            json!({ "module": 1, "data": { "ListPrice": 1 }, "related": related }),
+        )
+    };
+    let responses = session_with(
+        &["--check-schema".as_ref()],
+        &[
+            request(1, "load_module", json!({ "path": path })),
+            validate(2, json!({ "Rooms": [{ "RoomLength": "long" }] })),
+            validate(3, json!({ "Rooms": [{ "RoomLength": 12 }] })),
+        ],
+    );
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(responses[1]["error"]["data"]["exit_code"], 11);
+    let message = responses[1]["error"]["message"].as_str().unwrap();
+    assert!(message.contains("related.Rooms"), "{message}");
+    assert!(message.contains("Rooms[0].RoomLength"), "{message}");
+    assert!(responses[2]["result"]["outcome"].is_object());
+}
@@ -0,0 +1,93 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc,
+    time::Duration,
+};
+
+/// A module that reports an error on `ListPrice` for every listing
+const ERRORS: &str = r#"
+(module
+  (import "reso" "error" (func $error (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListPricetoo low")
+  (func (export "validate")
+    (call $error (i32.const 9) (i32.const 0) (i32.const 7) (i32.const 9))))
+"#;
+
+/// The same, but with a warning instead
+const WARNS: &str = r#"
+(module
+  (import "reso" "warn" (func $warn (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "ListPricetoo low")
+  (func (export "validate")
+    (call $warn (i32.const 9) (i32.const 0) (i32.const 7) (i32.const 9))))
+"#;
+
+#[test]
+fn changing_the_module_reruns_it_and_shows_what_changed() {
+    let dir = std::env::temp_dir().join(format!("watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = dir.join("module.wat");
+    std::fs::write(&module, ERRORS).unwrap();
+    let listing = dir.join("listing.json");
+    std::fs::write(&listing, r#"{"ListPrice": 1}"#).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webassembly-rules-poc"))
+        .arg("--webassembly")
+        .arg(&module)
+        .arg("--data")
+        .arg(&listing)
+        .arg("--watch")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stdout.lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    // Everything printed up to the next time the watch is waiting for a change
+    let run = || {
+        let mut lines = Vec::new();
+        loop {
+            let line = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            if line.starts_with("👀") {
+                return lines;
+            }
+            lines.push(line);
+        }
+    };
+
+    let first = run();
+    std::fs::write(&module, WARNS).unwrap();
+    let second = run();
+    child.kill().unwrap();
+    child.wait().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+
+    assert!(
+        !first.iter().any(|line| line.starts_with("📝")),
+        "{first:?}"
+    );
+    let changes = second
+        .iter()
+        .skip_while(|line| !line.starts_with("📝"))
+        .skip(1)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        [
+            "\x1b[31m-\x1b[0m ❗️ ListPrice: too low",
+            "\x1b[32m+\x1b[0m ⚠️ ListPrice: too low",
+        ],
+        "{second:?}"
+    );
+}